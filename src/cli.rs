@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -22,6 +22,14 @@ pub enum Commands {
         /// Do not print available values for each project
         #[arg(short = 'V', long)]
         no_values: bool,
+
+        /// Print templates with their source file and outputs instead of values
+        #[arg(short, long)]
+        templates: bool,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ListFormat::Tree)]
+        format: ListFormat,
     },
 
     /// Generate template files
@@ -37,6 +45,10 @@ pub enum Commands {
         #[arg(short, long)]
         random: bool,
 
+        /// Generate the templates once for every values file
+        #[arg(short, long, conflicts_with_all = ["values", "random"])]
+        all_values: bool,
+
         /// Only generate this template
         #[arg(short, long, value_name = "NAME")]
         template: Option<String>,
@@ -50,6 +62,72 @@ pub enum Commands {
         )]
         value_overrides: Vec<(String, String)>,
     },
+
+    /// Render a single template to stdout
+    Render {
+        /// Project to take the template and values from
+        project: String,
+
+        /// Template name, path to a template file or '-' to read from stdin
+        template: String,
+
+        /// Values to supply to the template
+        #[arg(short, long, value_name = "NAME")]
+        values: Option<String>,
+
+        /// Pick a random values file
+        #[arg(short, long)]
+        random: bool,
+
+        /// Override value manually
+        #[arg(
+            short = 's',
+            long = "set",
+            value_name = "KEY=VALUE",
+            value_parser = parse_key_val
+        )]
+        value_overrides: Vec<(String, String)>,
+    },
+
+    /// Print resolved values
+    Show {
+        /// Project the values belong to
+        project: String,
+
+        /// Values to resolve
+        values: String,
+
+        /// Override value manually
+        #[arg(
+            short = 's',
+            long = "set",
+            value_name = "KEY=VALUE",
+            value_parser = parse_key_val
+        )]
+        value_overrides: Vec<(String, String)>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ShowFormat::Toml)]
+        format: ShowFormat,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ListFormat {
+    /// Human readable tree
+    Tree,
+    /// One entry per line
+    Plain,
+    /// JSON including templates
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ShowFormat {
+    /// TOML table
+    Toml,
+    /// JSON object
+    Json,
 }
 
 fn parse_key_val(s: &str) -> Result<(String, String), String> {
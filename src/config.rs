@@ -1,12 +1,23 @@
 use crate::cli::Cli;
+use crate::import::{SCHEME_METADATA, extract_palette, load_pywal_colors, load_schemes};
+use crate::recolor::parse_color;
 use anyhow::{Context, Result, anyhow};
-use serde::{Deserialize, Deserializer, de::DeserializeOwned};
+use indexmap::IndexMap;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
 use std::fs;
+use std::marker::PhantomData;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::slice;
 
 // Parsed files
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TemplateMode {
     #[default]
@@ -14,46 +25,194 @@ pub enum TemplateMode {
     Append,
     Prepend,
 }
+impl fmt::Display for TemplateMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateMode::Replace => write!(f, "replace"),
+            TemplateMode::Append => write!(f, "append"),
+            TemplateMode::Prepend => write!(f, "prepend"),
+        }
+    }
+}
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkMode {
+    #[default]
+    Follow,
+    Replace,
+}
+impl fmt::Display for SymlinkMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymlinkMode::Follow => write!(f, "follow"),
+            SymlinkMode::Replace => write!(f, "replace"),
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Toml,
+    Css,
+    Xresources,
+}
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Yaml => write!(f, "yaml"),
+            OutputFormat::Toml => write!(f, "toml"),
+            OutputFormat::Css => write!(f, "css"),
+            OutputFormat::Xresources => write!(f, "xresources"),
+        }
+    }
+}
+/// Keys a template receives, either a selection of keys or new keys mapped to existing ones
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ValuesMap {
+    Select(Vec<String>),
+    Rename(IndexMap<String, String>),
+}
+/// An output path, optionally overriding the settings of its template
+#[derive(Debug)]
+enum OutputConfig {
+    Path(PathBuf),
+    Table(OutputTableConfig),
+}
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutputTableConfig {
+    path: PathBuf,
+    mode: Option<TemplateMode>,
+    merge_file: Option<PathBuf>,
+    #[serde(default)]
+    vars: IndexMap<String, Value>,
+    #[serde(default, deserialize_with = "permissions")]
+    permissions: Option<u32>,
+}
 #[derive(Debug, Deserialize)]
 struct TemplateConfig {
     #[serde(deserialize_with = "single_or_vec")]
-    out: Vec<PathBuf>,
+    out: Vec<OutputConfig>,
     file: Option<PathBuf>,
+    format: Option<OutputFormat>,
     #[serde(default)]
     mode: TemplateMode,
     merge_file: Option<PathBuf>,
+    values_map: Option<ValuesMap>,
+    #[serde(default)]
+    vars: IndexMap<String, Value>,
+    #[serde(default, deserialize_with = "permissions")]
+    permissions: Option<u32>,
+    #[serde(default)]
+    symlinks: SymlinkMode,
+    #[serde(default)]
+    recolor: IndexMap<String, String>,
+}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaletteConfig {
+    File(PathBuf),
+    Image(PathBuf),
 }
 #[derive(Debug, Deserialize)]
 struct ProjectConfig {
     values: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "single_or_vec")]
+    schemes: Vec<PathBuf>,
+    #[serde(default)]
+    palettes: BTreeMap<String, PaletteConfig>,
     templates: HashMap<String, TemplateConfig>,
 }
 #[derive(Debug, Deserialize)]
 struct ValuesFile {
     #[serde(flatten)]
-    values: HashMap<String, String>,
+    values: IndexMap<String, Value>,
     #[serde(default)]
-    vars: HashMap<String, String>,
+    vars: IndexMap<String, Value>,
 }
 
 // Runtime representation
 #[derive(Debug)]
-pub struct Template {
-    pub name: String,
-    pub contents: String,
-    pub out: Vec<PathBuf>,
+pub enum TemplateSource {
+    File {
+        path: PathBuf,
+        contents: FileContents,
+    },
+    Directory {
+        path: PathBuf,
+        files: Vec<TemplateFile>,
+    },
+    Format(OutputFormat),
+}
+/// A file in a directory template, with its path relative to the directory
+#[derive(Debug)]
+pub struct TemplateFile {
+    pub path: PathBuf,
+    pub contents: FileContents,
+    pub permissions: Option<u32>,
+}
+/// Text files are rendered, binary files are copied verbatim
+#[derive(Debug)]
+pub enum FileContents {
+    Text(String),
+    Binary(Vec<u8>),
+}
+/// An output of a template, its paths are expanded once their placeholders are filled
+#[derive(Debug)]
+pub struct TemplateOutput {
+    pub path: PathBuf,
     pub mode: TemplateMode,
     pub merge_path: Option<PathBuf>,
+    pub vars: IndexMap<String, Value>,
+    pub permissions: Option<u32>,
+    pub symlinks: SymlinkMode,
+    pub project_path: PathBuf,
+}
+#[derive(Debug)]
+pub struct Template {
+    pub name: String,
+    pub source: TemplateSource,
+    pub out: Vec<TemplateOutput>,
+    pub values_map: Option<ValuesMap>,
+    pub vars: IndexMap<String, Value>,
+    pub recolor: IndexMap<String, String>,
+}
+/// A value in a values file, either a string or a nested collection of values
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    String(String),
+    Array(Vec<Value>),
+    Table(IndexMap<String, Value>),
 }
 #[derive(Debug, Clone)]
 pub struct Values {
-    pub data: HashMap<String, String>,
-    pub vars: HashMap<String, String>,
+    pub data: IndexMap<String, Value>,
+    pub vars: IndexMap<String, Value>,
+}
+/// Values of a project, values files are only resolved once they are selected
+#[derive(Debug)]
+pub enum ProjectValues {
+    File {
+        path: PathBuf,
+        values: IndexMap<String, Value>,
+        vars: IndexMap<String, Value>,
+    },
+    Palette {
+        config: PaletteConfig,
+        project_path: PathBuf,
+        required: Option<Vec<String>>,
+    },
+    Loaded(Values),
 }
 #[derive(Debug)]
 pub struct Project {
     pub templates: Vec<Template>,
-    pub values: BTreeMap<String, Values>,
+    pub values: BTreeMap<String, ProjectValues>,
 }
 pub type Projects = BTreeMap<String, Project>;
 #[derive(Debug)]
@@ -61,6 +220,204 @@ pub struct Config {
     pub projects: Projects,
 }
 
+impl Value {
+    /// Retrieve a child by table key or array index
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Table(table) => table.get(key),
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+            Value::String(_) => None,
+        }
+    }
+
+    /// Key and value pairs of a table, or index and item pairs of an array
+    pub fn entries(&self) -> Option<Vec<(String, &Value)>> {
+        match self {
+            Value::Table(table) => Some(
+                table
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value))
+                    .collect(),
+            ),
+            Value::Array(items) => Some(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, item)| (index.to_string(), item))
+                    .collect(),
+            ),
+            Value::String(_) => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string, number, boolean, array or table")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Value, E> {
+                Ok(Value::String(value.to_string()))
+            }
+
+            fn visit_string<E: de::Error>(self, value: String) -> Result<Value, E> {
+                Ok(Value::String(value))
+            }
+
+            // Scalars are used as their textual representation
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<Value, E> {
+                Ok(Value::String(value.to_string()))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Value, E> {
+                Ok(Value::String(value.to_string()))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Value, E> {
+                Ok(Value::String(value.to_string()))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Value, E> {
+                Ok(Value::String(value.to_string()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+                let mut items = Vec::new();
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+
+                Ok(Value::Array(items))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+                let mut table = IndexMap::new();
+                while let Some((key, value)) = map.next_entry()? {
+                    table.insert(key, value);
+                }
+
+                Ok(Value::Table(table))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+// Not untagged, which would hide errors of the table fields
+impl<'de> Deserialize<'de> for OutputConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OutputConfigVisitor;
+
+        impl<'de> Visitor<'de> for OutputConfigVisitor {
+            type Value = OutputConfig;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a path or a table with a path")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<OutputConfig, E> {
+                Ok(OutputConfig::Path(PathBuf::from(value)))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<OutputConfig, A::Error> {
+                OutputTableConfig::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(OutputConfig::Table)
+            }
+        }
+
+        deserializer.deserialize_any(OutputConfigVisitor)
+    }
+}
+
+impl Values {
+    /// Set a value, creating intermediate tables for dotted keys
+    pub fn set(&mut self, key: &str, value: String) -> Result<()> {
+        if self.data.contains_key(key) || !key.contains('.') {
+            self.data.insert(key.to_string(), Value::String(value));
+            return Ok(());
+        }
+
+        let mut segments: Vec<&str> = key.split('.').collect();
+        let last = segments.pop().unwrap();
+
+        let mut table = &mut self.data;
+        for segment in segments {
+            let entry = table
+                .entry(segment.to_string())
+                .or_insert_with(|| Value::Table(IndexMap::new()));
+
+            table = match entry {
+                Value::Table(inner) => inner,
+                _ => {
+                    return Err(anyhow!(
+                        "Can not set '{}': '{}' is not a table",
+                        key,
+                        segment
+                    ));
+                }
+            };
+        }
+        table.insert(last.to_string(), Value::String(value));
+
+        Ok(())
+    }
+}
+
+impl ProjectValues {
+    /// Resolve references, environment variables and commands of a values file, or load a palette
+    pub fn resolve(&self) -> Result<Values> {
+        let (path, raw_values, raw_vars) = match self {
+            ProjectValues::File { path, values, vars } => (path, values, vars),
+            ProjectValues::Palette {
+                config,
+                project_path,
+                required,
+            } => return load_palette(config, project_path, required),
+            ProjectValues::Loaded(values) => return Ok(values.clone()),
+        };
+
+        // Resolve vars and values, following references between them
+        let mut resolver = ValuesResolver::new(path, raw_vars, raw_values);
+        let vars: IndexMap<String, Value> = raw_vars
+            .keys()
+            .map(|key| {
+                Ok((
+                    key.clone(),
+                    resolver.resolve(Reference::Var, slice::from_ref(key))?,
+                ))
+            })
+            .collect::<Result<_>>()?;
+        let data: IndexMap<String, Value> = raw_values
+            .keys()
+            .map(|key| {
+                Ok((
+                    key.clone(),
+                    resolver.resolve(Reference::Value, slice::from_ref(key))?,
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Values { data, vars })
+    }
+}
+
+/// Look up a key in a pool of values, falling back to a dotted path into nested collections
+pub fn lookup<'a>(pool: &'a IndexMap<String, Value>, key: &str) -> Option<&'a Value> {
+    pool.get(key).or_else(|| {
+        let mut segments = key.split('.');
+        let first = pool.get(segments.next()?)?;
+
+        segments.try_fold(first, |value, segment| value.get(segment))
+    })
+}
+
 impl Config {
     pub fn parse(cli: &Cli) -> Result<Self> {
         let config_directory = cli
@@ -84,8 +441,12 @@ fn load_project(path: &Path) -> Result<(String, Project)> {
     let project_name = path.file_name().unwrap().to_string_lossy().to_string();
 
     // Config
-    let config_path = path.join("config.toml");
-    let config: ProjectConfig = read_toml(&config_path).with_context(|| {
+    let config_path = ["config.toml", "config.json", "config.yaml", "config.yml"]
+        .iter()
+        .map(|file_name| path.join(file_name))
+        .find(|config_path| config_path.is_file())
+        .unwrap_or_else(|| path.join("config.toml"));
+    let config: ProjectConfig = read_data(&config_path).with_context(|| {
         format!(
             "Failed to read project config file at path '{}'",
             path.display()
@@ -99,49 +460,112 @@ fn load_project(path: &Path) -> Result<(String, Project)> {
         .templates
         .into_iter()
         .map(|(name, template_config)| {
-            // Template file equals the name if not supplied
-            let template_file = match template_config.file {
-                Some(file) => file,
-                None => name.clone().into(),
+            let source = match (template_config.format, template_config.file) {
+                // Formatted templates serialize the values directly
+                (Some(_), Some(_)) => {
+                    return Err(anyhow!(
+                        "Template '{}' can not declare both 'file' and 'format'",
+                        name
+                    ));
+                }
+                (Some(format), None) => TemplateSource::Format(format),
+                (None, file) => {
+                    // Template file equals the name if not supplied
+                    let template_path = expand_path(
+                        &file.unwrap_or_else(|| name.clone().into()),
+                        &home_dir,
+                        &templates_path,
+                    )?;
+
+                    // Read template or all files of a template directory
+                    if template_path.is_dir() {
+                        let mut files = Vec::new();
+                        load_template_directory(&template_path, Path::new(""), &mut files)?;
+
+                        TemplateSource::Directory {
+                            path: template_path,
+                            files,
+                        }
+                    } else {
+                        TemplateSource::File {
+                            contents: read_template_file(&template_path)?,
+                            path: template_path,
+                        }
+                    }
+                }
             };
-            let template_path = templates_path.join(template_file);
 
-            // Expand home directory
-            let out: Vec<PathBuf> = template_config
+            // Outputs fall back to the mode, merge file and permissions of the template
+            let out = template_config
                 .out
                 .into_iter()
-                .map(|path| {
-                    if path.starts_with("~") {
-                        home_dir.join(path.strip_prefix("~").unwrap())
-                    } else {
-                        path
-                    }
+                .map(|output_config| -> Result<_> {
+                    let (out_path, mode, merge_file, vars, permissions) = match output_config {
+                        OutputConfig::Path(out_path) => {
+                            (out_path, None, None, IndexMap::new(), None)
+                        }
+                        OutputConfig::Table(OutputTableConfig {
+                            path,
+                            mode,
+                            merge_file,
+                            vars,
+                            permissions,
+                        }) => (path, mode, merge_file, vars, permissions),
+                    };
+
+                    // Paths are resolved against the project directory when rendering them
+                    Ok(TemplateOutput {
+                        path: out_path,
+                        mode: mode.unwrap_or(template_config.mode),
+                        merge_path: merge_file.or_else(|| template_config.merge_file.clone()),
+                        vars,
+                        permissions: permissions.or(template_config.permissions),
+                        symlinks: template_config.symlinks,
+                        project_path: path.to_path_buf(),
+                    })
                 })
-                .collect();
+                .collect::<Result<Vec<_>>>()?;
 
-            // Read template
-            let template_contents = fs::read_to_string(&template_path).with_context(|| {
-                format!(
-                    "Failed to read template file at path '{}'",
-                    template_path.display()
-                )
-            })?;
+            // Directory templates write whole files
+            if let TemplateSource::Directory { .. } = source
+                && out
+                    .iter()
+                    .any(|output| !matches!(output.mode, TemplateMode::Replace))
+            {
+                return Err(anyhow!(
+                    "Directory template '{}' only supports the replace mode",
+                    name
+                ));
+            }
 
-            // Expand merge file path if it was declared
-            let merge_path = template_config.merge_file.map(|path| {
-                if path.starts_with("~") {
-                    home_dir.join(path.strip_prefix("~").unwrap())
-                } else {
-                    path
-                }
-            });
+            // Recoloring replaces colors of template files
+            if let TemplateSource::Format(_) = source
+                && !template_config.recolor.is_empty()
+            {
+                return Err(anyhow!(
+                    "Template '{}' can not declare both 'format' and 'recolor'",
+                    name
+                ));
+            }
+            if let Some(color) = template_config
+                .recolor
+                .keys()
+                .find(|color| parse_color(color).is_none())
+            {
+                return Err(anyhow!(
+                    "Invalid color '{}' to recolor in template '{}'",
+                    color,
+                    name
+                ));
+            }
 
             Ok(Template {
                 out,
                 name,
-                mode: template_config.mode,
-                contents: template_contents,
-                merge_path,
+                source,
+                values_map: template_config.values_map,
+                vars: template_config.vars,
+                recolor: template_config.recolor,
             })
         })
         .collect::<Result<_>>()?;
@@ -149,7 +573,7 @@ fn load_project(path: &Path) -> Result<(String, Project)> {
 
     // Values
     let values_path = path.join("values");
-    let values = fs::read_dir(&values_path)
+    let mut values = fs::read_dir(&values_path)
         .with_context(|| {
             format!(
                 "Failed to read values directory at path '{}'",
@@ -157,47 +581,158 @@ fn load_project(path: &Path) -> Result<(String, Project)> {
             )
         })?
         .map(|entry| load_values(&entry?.path(), &config.values))
-        .collect::<Result<_>>()?;
+        .collect::<Result<BTreeMap<_, _>>>()?;
+
+    // Base16 and base24 schemes become values as well
+    for scheme_path in &config.schemes {
+        let scheme_path = expand_path(scheme_path, &home_dir, path)?;
+
+        for (scheme_name, scheme_file, scheme_values) in load_schemes(&scheme_path)? {
+            validate_scheme(&scheme_file, &scheme_values.data, &config.values)?;
+
+            if values.contains_key(&scheme_name) {
+                return Err(anyhow!(
+                    "Scheme '{}' at path '{}' conflicts with values of the same name",
+                    scheme_name,
+                    scheme_file.display()
+                ));
+            }
+            values.insert(scheme_name, ProjectValues::Loaded(scheme_values));
+        }
+    }
+
+    // Palettes from pywal style color files or extracted from images, loaded once they are selected
+    for (palette_name, palette_config) in config.palettes {
+        if values.contains_key(&palette_name) {
+            return Err(anyhow!(
+                "Palette '{}' conflicts with values of the same name",
+                palette_name
+            ));
+        }
+        values.insert(
+            palette_name,
+            ProjectValues::Palette {
+                config: palette_config,
+                project_path: path.to_path_buf(),
+                required: config.values.clone(),
+            },
+        );
+    }
 
     Ok((project_name, Project { templates, values }))
 }
 
-fn load_values(path: &Path, values: &Option<Vec<String>>) -> Result<(String, Values)> {
+fn load_palette(
+    palette_config: &PaletteConfig,
+    project_path: &Path,
+    required: &Option<Vec<String>>,
+) -> Result<Values> {
+    let home_dir = dirs::home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
+    let (palette_path, palette_values) = match palette_config {
+        PaletteConfig::File(file) => {
+            let file = expand_path(file, &home_dir, project_path)?;
+            let palette_values = load_pywal_colors(&file)?;
+
+            (file, palette_values)
+        }
+        PaletteConfig::Image(image) => {
+            let image = expand_path(image, &home_dir, project_path)?;
+            let palette_values = extract_palette(&image)?;
+
+            (image, palette_values)
+        }
+    };
+    validate_values(&palette_path, &palette_values.data, required)?;
+
+    Ok(palette_values)
+}
+
+/// Read all files below a template directory, sorted by their path
+fn load_template_directory(
+    directory: &Path,
+    relative_path: &Path,
+    files: &mut Vec<TemplateFile>,
+) -> Result<()> {
+    let path = directory.join(relative_path);
+    let mut entries = fs::read_dir(&path)
+        .with_context(|| {
+            format!(
+                "Failed to read template directory at path '{}'",
+                path.display()
+            )
+        })?
+        .map(|entry| Ok(entry?.file_name()))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+
+    for file_name in entries {
+        let relative_path = relative_path.join(file_name);
+        let path = directory.join(&relative_path);
+
+        if path.is_dir() {
+            load_template_directory(directory, &relative_path, files)?;
+            continue;
+        }
+
+        let contents = read_template_file(&path)?;
+        let metadata = fs::metadata(&path).with_context(|| {
+            format!("Failed to read template file at path '{}'", path.display())
+        })?;
+
+        files.push(TemplateFile {
+            path: relative_path,
+            contents,
+            permissions: file_permissions(&metadata),
+        });
+    }
+
+    Ok(())
+}
+
+fn read_template_file(path: &Path) -> Result<FileContents> {
+    let bytes = fs::read(path)
+        .with_context(|| format!("Failed to read template file at path '{}'", path.display()))?;
+
+    Ok(match String::from_utf8(bytes) {
+        Ok(text) => FileContents::Text(text),
+        Err(error) => FileContents::Binary(error.into_bytes()),
+    })
+}
+
+#[cfg(unix)]
+fn file_permissions(metadata: &fs::Metadata) -> Option<u32> {
+    Some(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn file_permissions(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+fn load_values(path: &Path, values: &Option<Vec<String>>) -> Result<(String, ProjectValues)> {
     let values_name = path.file_stem().unwrap().to_string_lossy().to_string();
-    let values_file: ValuesFile = read_toml(path)
+    let values_file: ValuesFile = read_data(path)
         .with_context(|| format!("Failed to read values file at path '{}'", path.display()))?;
 
-    // Resolve values from vars section
-    let values_data: HashMap<String, String> = values_file
-        .values
-        .into_iter()
-        .map(|(key, value)| {
-            // If the value starts with a '$' it is a var
-            let resolved_value = if let Some(var) = value.strip_prefix("$") {
-                values_file
-                    .vars
-                    .get(var)
-                    .ok_or_else(|| {
-                        anyhow!(
-                            "Data '{}' not defined in values file at path '{}'",
-                            var,
-                            path.display()
-                        )
-                    })?
-                    .clone()
-            // If the value starts with a '\$' it should be a literal dollar sign
-            } else if let Some(rest) = value.strip_prefix("\\$") {
-                format!("${}", rest)
-            // Otherwise it is a literal value
-            } else {
-                value
-            };
+    // Keys are known without resolving the values, which may run commands
+    validate_values(path, &values_file.values, values)?;
 
-            Ok((key, resolved_value))
-        })
-        .collect::<Result<_>>()?;
+    Ok((
+        values_name,
+        ProjectValues::File {
+            path: path.to_path_buf(),
+            values: values_file.values,
+            vars: values_file.vars,
+        },
+    ))
+}
 
-    // Validate values
+/// Check that values match the keys required by the project config
+fn validate_values(
+    path: &Path,
+    values_data: &IndexMap<String, Value>,
+    values: &Option<Vec<String>>,
+) -> Result<()> {
     if let Some(required_values) = values.as_ref() {
         // Find missing values
         let missing_values: Vec<_> = required_values
@@ -235,38 +770,574 @@ fn load_values(path: &Path, values: &Option<Vec<String>>) -> Result<(String, Val
         }
     }
 
-    Ok((
-        values_name,
-        Values {
-            data: values_data,
-            vars: values_file.vars,
-        },
-    ))
+    Ok(())
+}
+
+/// Check the colors of a scheme against the project config, its name and author only if required
+fn validate_scheme(
+    path: &Path,
+    scheme_data: &IndexMap<String, Value>,
+    values: &Option<Vec<String>>,
+) -> Result<()> {
+    let colors: IndexMap<String, Value> = scheme_data
+        .iter()
+        .filter(|(key, _)| {
+            !SCHEME_METADATA.contains(&key.as_str())
+                || values
+                    .as_ref()
+                    .is_some_and(|required_values| required_values.contains(key))
+        })
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    validate_values(path, &colors, values)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Reference {
+    Var,
+    Value,
+}
+
+/// Resolves '$name' and '${name}' references within a values file, looking up vars before values
+struct ValuesResolver<'a> {
+    path: &'a Path,
+    raw_vars: &'a IndexMap<String, Value>,
+    raw_values: &'a IndexMap<String, Value>,
+    resolved: HashMap<(Reference, Vec<String>), Value>,
+    stack: Vec<(Reference, Vec<String>)>,
+}
+
+impl<'a> ValuesResolver<'a> {
+    fn new(
+        path: &'a Path,
+        raw_vars: &'a IndexMap<String, Value>,
+        raw_values: &'a IndexMap<String, Value>,
+    ) -> Self {
+        Self {
+            path,
+            raw_vars,
+            raw_values,
+            resolved: HashMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    fn resolve(&mut self, kind: Reference, segments: &[String]) -> Result<Value> {
+        let id = (kind, segments.to_vec());
+        if let Some(resolved) = self.resolved.get(&id) {
+            return Ok(resolved.clone());
+        }
+
+        // Detect cycles by checking the references currently being resolved
+        if let Some(cycle_start) = self.stack.iter().position(|entry| *entry == id) {
+            let cycle: Vec<_> = self.stack[cycle_start..]
+                .iter()
+                .map(|(_, segments)| segments.join("."))
+                .chain([segments.join(".")])
+                .collect();
+
+            return Err(anyhow!(
+                "Cyclic reference in values file at path '{}': {}",
+                self.path.display(),
+                cycle.join(" -> ")
+            ));
+        }
+
+        let raw_pool = match kind {
+            Reference::Var => self.raw_vars,
+            Reference::Value => self.raw_values,
+        };
+        let raw = segments[1..]
+            .iter()
+            .try_fold(&raw_pool[&segments[0]], |value, segment| value.get(segment))
+            .unwrap();
+
+        self.stack.push(id.clone());
+        let resolved = match raw {
+            Value::String(raw) => self.resolve_raw(raw),
+            Value::Array(items) => (0..items.len())
+                .map(|index| self.resolve(kind, &[segments, &[index.to_string()]].concat()))
+                .collect::<Result<_>>()
+                .map(Value::Array),
+            Value::Table(table) => table
+                .keys()
+                .map(|key| {
+                    Ok((
+                        key.clone(),
+                        self.resolve(kind, &[segments, slice::from_ref(key)].concat())?,
+                    ))
+                })
+                .collect::<Result<_>>()
+                .map(Value::Table),
+        };
+        self.stack.pop();
+
+        let resolved = resolved?;
+        self.resolved.insert(id, resolved.clone());
+
+        Ok(resolved)
+    }
+
+    fn resolve_reference(&mut self, name: &str) -> Result<Value> {
+        for (kind, raw_pool) in [
+            (Reference::Var, self.raw_vars),
+            (Reference::Value, self.raw_values),
+        ] {
+            if raw_pool.contains_key(name) {
+                return self.resolve(kind, &[name.to_string()]);
+            }
+            if lookup(raw_pool, name).is_some() {
+                let segments: Vec<String> = name.split('.').map(String::from).collect();
+                return self.resolve(kind, &segments);
+            }
+        }
+
+        Err(anyhow!(
+            "Data '{}' not defined in values file at path '{}'",
+            name,
+            self.path.display()
+        ))
+    }
+
+    fn resolve_raw(&mut self, raw: &str) -> Result<Value> {
+        // If the value starts with 'env:' or 'cmd:' it is read from an external source
+        if let Some(resolved) = resolve_source(raw, self.path)? {
+            return Ok(Value::String(resolved));
+        }
+
+        // If the value starts with a '\' followed by a source prefix it should be taken literally
+        if let Some(rest) = raw
+            .strip_prefix("\\")
+            .filter(|rest| rest.starts_with("env:") || rest.starts_with("cmd:"))
+        {
+            return self.interpolate(rest).map(Value::String);
+        }
+
+        // If the whole value is '$name' it is a reference
+        if let Some(name) = raw.strip_prefix("$").filter(|name| !name.starts_with("{")) {
+            return self.resolve_reference(name);
+        }
+
+        self.interpolate(raw).map(Value::String)
+    }
+
+    fn interpolate(&mut self, raw: &str) -> Result<String> {
+        let mut result = String::with_capacity(raw.len());
+        let mut rest = raw;
+
+        while let Some(index) = rest.find(['$', '\\']) {
+            result.push_str(&rest[..index]);
+            rest = &rest[index..];
+
+            // '\$' is a literal dollar sign
+            if let Some(after) = rest.strip_prefix("\\$") {
+                result.push('$');
+                rest = after;
+            // '${name}' is replaced with the referenced data
+            } else if let Some(after) = rest.strip_prefix("${") {
+                let end = after.find('}').ok_or_else(|| {
+                    anyhow!(
+                        "Unterminated reference '{}' in values file at path '{}'",
+                        raw,
+                        self.path.display()
+                    )
+                })?;
+
+                let name = &after[..end];
+                match self.resolve_reference(name)? {
+                    Value::String(value) => result.push_str(&value),
+                    _ => {
+                        return Err(anyhow!(
+                            "Data '{}' interpolated in values file at path '{}' is not a string",
+                            name,
+                            self.path.display()
+                        ));
+                    }
+                }
+                rest = &after[end + 1..];
+            } else {
+                let character = rest.chars().next().unwrap();
+                result.push(character);
+                rest = &rest[character.len_utf8()..];
+            }
+        }
+        result.push_str(rest);
+
+        Ok(result)
+    }
+}
+
+fn resolve_source(value: &str, path: &Path) -> Result<Option<String>> {
+    // 'env:VAR' reads an environment variable
+    if let Some(var) = value.strip_prefix("env:") {
+        return env::var(var).map(Some).with_context(|| {
+            format!(
+                "Environment variable '{}' referenced in values file at path '{}' is not set",
+                var,
+                path.display()
+            )
+        });
+    }
+
+    // 'cmd:COMMAND' reads the output of a shell command
+    if let Some(command) = value.strip_prefix("cmd:") {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .with_context(|| {
+                format!(
+                    "Failed to run command '{}' referenced in values file at path '{}'",
+                    command,
+                    path.display()
+                )
+            })?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Command '{}' referenced in values file at path '{}' failed with {}: {}",
+                command,
+                path.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        return Ok(Some(
+            String::from_utf8_lossy(&output.stdout)
+                .trim_end_matches(['\n', '\r'])
+                .to_string(),
+        ));
+    }
+
+    Ok(None)
+}
+
+/// Expand a leading '~' and '$VAR' or '${VAR}' variables, resolving relative paths against 'base_dir'
+pub fn expand_path(path: &Path, home_dir: &Path, base_dir: &Path) -> Result<PathBuf> {
+    let path = match path.strip_prefix("~") {
+        Ok(rest) => home_dir.join(rest),
+        Err(_) => path.to_path_buf(),
+    };
+
+    let raw = path.to_string_lossy();
+    let mut expanded = String::with_capacity(raw.len());
+    let mut rest = raw.as_ref();
+
+    while let Some(index) = rest.find('$') {
+        expanded.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+
+        // Variable names are either enclosed in braces or run until the first non identifier character
+        let (name, after) = match rest.strip_prefix('{') {
+            Some(braced) => {
+                let end = braced
+                    .find('}')
+                    .ok_or_else(|| anyhow!("Unterminated variable in path '{}'", path.display()))?;
+
+                (&braced[..end], &braced[end + 1..])
+            }
+            None => {
+                let end = rest
+                    .find(|character: char| !character.is_ascii_alphanumeric() && character != '_')
+                    .unwrap_or(rest.len());
+
+                (&rest[..end], &rest[end..])
+            }
+        };
+
+        if name.is_empty() {
+            expanded.push('$');
+            continue;
+        }
+
+        expanded.push_str(&env_path_var(name, home_dir).ok_or_else(|| {
+            anyhow!(
+                "Environment variable '{}' in path '{}' is not set",
+                name,
+                path.display()
+            )
+        })?);
+        rest = after;
+    }
+    expanded.push_str(rest);
+
+    Ok(base_dir.join(expanded))
+}
+
+/// Read an environment variable, falling back to the specified defaults for the home and XDG directories
+fn env_path_var(name: &str, home_dir: &Path) -> Option<String> {
+    let default = match name {
+        "HOME" => Some(home_dir.to_path_buf()),
+        "XDG_CONFIG_HOME" => Some(home_dir.join(".config")),
+        "XDG_DATA_HOME" => Some(home_dir.join(".local/share")),
+        "XDG_STATE_HOME" => Some(home_dir.join(".local/state")),
+        "XDG_CACHE_HOME" => Some(home_dir.join(".cache")),
+        _ => None,
+    };
+
+    env::var(name)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .or_else(|| default.map(|default| default.to_string_lossy().to_string()))
 }
 
-fn read_toml<T: DeserializeOwned>(path: &Path) -> Result<T> {
+/// Read a JSON, YAML or TOML file depending on its extension, defaulting to TOML
+pub fn read_data<T: DeserializeOwned>(path: &Path) -> Result<T> {
     let contents = fs::read_to_string(path)
         .with_context(|| format!("Failed to read file '{}'", path.display()))?;
-    let parsed = toml::from_str(&contents)
-        .with_context(|| format!("Failed to parse TOML in '{}'", path.display()))?;
+
+    let parsed = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse JSON in '{}'", path.display()))?,
+        Some("yaml" | "yml") => serde_norway::from_str(&contents)
+            .with_context(|| format!("Failed to parse YAML in '{}'", path.display()))?,
+        _ => toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse TOML in '{}'", path.display()))?,
+    };
 
     Ok(parsed)
 }
 
-fn single_or_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+/// Read file permissions from octal digits given as a string like '0600' or an integer like 600
+fn permissions<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
 where
     D: Deserializer<'de>,
-    T: Deserialize<'de>,
 {
     #[derive(Deserialize)]
     #[serde(untagged)]
-    enum OneOrMany<T> {
-        One(T),
-        Many(Vec<T>),
+    enum Permissions {
+        Octal(String),
+        Number(u32),
+    }
+
+    // Integers are read as octal digits, so both '0755' and 755 mean rwxr-xr-x
+    let octal = match Permissions::deserialize(deserializer)? {
+        Permissions::Octal(octal) => octal,
+        Permissions::Number(number) => number.to_string(),
+    };
+
+    u32::from_str_radix(octal.trim_start_matches("0o"), 8)
+        .ok()
+        .filter(|permissions| *permissions <= 0o777)
+        .map(Some)
+        .ok_or_else(|| {
+            de::Error::custom(format!(
+                "invalid file permissions '{}', expected octal digits between 000 and 777",
+                octal
+            ))
+        })
+}
+
+fn single_or_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    // Not untagged, which would hide errors of the items
+    struct SingleOrVecVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for SingleOrVecVisitor<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a single item or an array of items")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Vec<T>, E> {
+            T::deserialize(value.into_deserializer()).map(|v| vec![v])
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Vec<T>, A::Error> {
+            T::deserialize(de::value::MapAccessDeserializer::new(map)).map(|v| vec![v])
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Vec<T>, A::Error> {
+            Vec::deserialize(de::value::SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(SingleOrVecVisitor(PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct PermissionsConfig {
+        #[serde(default, deserialize_with = "permissions")]
+        permissions: Option<u32>,
     }
 
-    match OneOrMany::deserialize(deserializer)? {
-        OneOrMany::One(v) => Ok(vec![v]),
-        OneOrMany::Many(v) => Ok(v),
+    fn parse_permissions(toml: &str) -> Result<Option<u32>, toml::de::Error> {
+        toml::from_str::<PermissionsConfig>(toml).map(|config| config.permissions)
+    }
+
+    fn resolve(toml: &str) -> Result<Values> {
+        let values_file: ValuesFile = toml::from_str(toml).unwrap();
+
+        ProjectValues::File {
+            path: PathBuf::from("values.toml"),
+            values: values_file.values,
+            vars: values_file.vars,
+        }
+        .resolve()
+    }
+
+    fn string<'a>(values: &'a Values, key: &str) -> &'a str {
+        match lookup(&values.data, key) {
+            Some(Value::String(value)) => value,
+            value => panic!("'{}' is not a string: {:?}", key, value),
+        }
+    }
+
+    #[test]
+    fn references_are_resolved_recursively() {
+        let values = resolve(
+            r##"
+            accent = "$blue"
+            border = "2px solid ${accent}"
+            nested = { color = "${dark}" }
+            tone = "$nested.color"
+
+            [vars]
+            blue = "$navy"
+            navy = "#000080"
+            dark = "$accent"
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(string(&values, "accent"), "#000080");
+        assert_eq!(string(&values, "border"), "2px solid #000080");
+        assert_eq!(string(&values, "nested.color"), "#000080");
+        assert_eq!(string(&values, "tone"), "#000080");
+    }
+
+    #[test]
+    fn escaped_references_are_literal() {
+        let values = resolve(
+            r##"
+            x = "1"
+            y = '\${x} is ${x}'
+            z = '\env:HOME'
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(string(&values, "y"), "${x} is 1");
+        assert_eq!(string(&values, "z"), "env:HOME");
+    }
+
+    #[test]
+    fn cyclic_references_are_errors() {
+        let error = resolve(
+            r#"
+            a = "$b"
+            b = "prefix ${a}"
+            "#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Cyclic reference in values file at path 'values.toml': a -> b -> a"
+        );
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct OutConfig {
+        #[serde(deserialize_with = "single_or_vec")]
+        out: Vec<OutputConfig>,
+    }
+
+    fn parse_out(toml: &str) -> Result<Vec<OutputConfig>, toml::de::Error> {
+        toml::from_str::<OutConfig>(toml).map(|config| config.out)
+    }
+
+    #[test]
+    fn out_accepts_paths_and_tables() {
+        let out = parse_out(r#"out = "a""#).unwrap();
+        assert!(matches!(&out[..], [OutputConfig::Path(path)] if path == Path::new("a")));
+
+        let out = parse_out(r#"out = ["a", { path = "b", mode = "append" }]"#).unwrap();
+        assert!(matches!(
+            &out[..],
+            [
+                OutputConfig::Path(_),
+                OutputConfig::Table(OutputTableConfig {
+                    mode: Some(TemplateMode::Append),
+                    ..
+                })
+            ]
+        ));
+    }
+
+    #[test]
+    fn out_reports_table_field_errors() {
+        let error = parse_out(r#"out = [{ path = "x", mode = "apend" }]"#).unwrap_err();
+        assert!(error.to_string().contains("apend"), "{}", error);
+
+        let error = parse_out(r#"out = { path = "x", merge_fle = "y" }"#).unwrap_err();
+        assert!(error.to_string().contains("merge_fle"), "{}", error);
+    }
+
+    #[test]
+    fn permissions_are_octal_digits() {
+        assert_eq!(
+            parse_permissions(r#"permissions = "0600""#).unwrap(),
+            Some(0o600)
+        );
+        assert_eq!(
+            parse_permissions(r#"permissions = "0o644""#).unwrap(),
+            Some(0o644)
+        );
+        assert_eq!(parse_permissions("permissions = 755").unwrap(), Some(0o755));
+        assert_eq!(parse_permissions("").unwrap(), None);
+    }
+
+    #[test]
+    fn permissions_reject_invalid_modes() {
+        for toml in [
+            "permissions = 1755",
+            "permissions = 789",
+            r#"permissions = "rwx""#,
+            r#"permissions = "4755""#,
+        ] {
+            assert!(parse_permissions(toml).is_err(), "{} was accepted", toml);
+        }
+    }
+
+    #[test]
+    fn scheme_metadata_is_only_checked_if_required() {
+        let scheme_data: IndexMap<String, Value> = [
+            ("base00", "#181818"),
+            ("base01", "#282828"),
+            ("scheme", "Default Dark"),
+            ("author", "Chris Kempson"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+        .collect();
+        let path = Path::new("schemes/default-dark.yaml");
+
+        let colors = Some(vec!["base00".to_string(), "base01".to_string()]);
+        assert!(validate_scheme(path, &scheme_data, &colors).is_ok());
+
+        let with_name = Some(vec![
+            "base00".to_string(),
+            "base01".to_string(),
+            "scheme".to_string(),
+        ]);
+        assert!(validate_scheme(path, &scheme_data, &with_name).is_ok());
+
+        let missing = Some(vec!["base00".to_string()]);
+        let error = validate_scheme(path, &scheme_data, &missing).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unspecified keys in values file at path 'schemes/default-dark.yaml': base01"
+        );
     }
 }
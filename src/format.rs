@@ -0,0 +1,58 @@
+use crate::config::{OutputFormat, Value};
+use anyhow::Result;
+use indexmap::IndexMap;
+
+pub fn format_values(data: &IndexMap<String, Value>, format: OutputFormat) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Json => format!("{}\n", serde_json::to_string_pretty(data)?),
+        OutputFormat::Yaml => serde_norway::to_string(data)?,
+        OutputFormat::Toml => toml::to_string(data)?,
+        OutputFormat::Css => {
+            let properties: String = flatten(data, "-")
+                .into_iter()
+                .map(|(key, value)| format!("  --{}: {};\n", key, value))
+                .collect();
+
+            format!(":root {{\n{}}}\n", properties)
+        }
+        OutputFormat::Xresources => flatten(data, ".")
+            .into_iter()
+            .map(|(key, value)| format!("*.{}: {}\n", key, value))
+            .collect(),
+    })
+}
+
+/// Flatten nested values into key and value pairs, joining nested keys with 'separator'
+fn flatten(data: &IndexMap<String, Value>, separator: &str) -> Vec<(String, String)> {
+    fn flatten_into(
+        prefix: String,
+        value: &Value,
+        separator: &str,
+        result: &mut Vec<(String, String)>,
+    ) {
+        match value.entries() {
+            Some(entries) => {
+                for (key, value) in entries {
+                    flatten_into(
+                        format!("{}{}{}", prefix, separator, key),
+                        value,
+                        separator,
+                        result,
+                    );
+                }
+            }
+            None => {
+                if let Value::String(value) = value {
+                    result.push((prefix, value.clone()));
+                }
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    for (key, value) in data {
+        flatten_into(key.clone(), value, separator, &mut result);
+    }
+
+    result
+}
@@ -1,24 +1,26 @@
-use crate::config::{Config, Template, TemplateMode, Values};
+use crate::config::{Config, Project, SymlinkMode, Template, TemplateMode, TemplateSource, Values};
+use crate::template::{TemplateMeta, render_directory, render_paths, render_source};
 use anyhow::{Context, Result, anyhow};
+use indexmap::IndexMap;
 use rand::seq::IteratorRandom;
-use regex::Regex;
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use std::sync::LazyLock;
-
-static TEMPLATE_REGEX: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"\{\{\s*([^\s]+)\s*\}\}").unwrap());
-static REPEAT_REGEX: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"(?m)^<\{\s*repeat\s+([^\s]+)\s*\}>$").unwrap());
-static ENDREPEAT_REGEX: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"^<\{\s*endrepeat\s*\}>$").unwrap());
+use std::io;
+#[cfg(unix)]
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::process;
+use std::str;
 
 pub fn generate(
     project_name: &str,
     values_name: Option<&str>,
     value_overrides: &[(String, String)],
     random_values: bool,
+    all_values: bool,
     template_name: Option<&str>,
     config: &Config,
 ) -> Result<()> {
@@ -30,48 +32,32 @@ pub fn generate(
         }
     };
 
-    // If 'values_name' is not passed, choose a random one
-    let random_choice: &String;
-    let values_name = if let Some(name) = values_name {
-        name
-    } else if random_values {
-        random_choice = project
-            .values
-            .keys()
-            .choose(&mut rand::rng())
-            .ok_or_else(|| anyhow!("Project '{}' has no values", project_name))?;
+    // Either take all values or the passed one, choosing a random one if none was passed
+    let values_names: Vec<&str> = if all_values {
+        if project.values.is_empty() {
+            return Err(anyhow!("Project '{}' has no values", project_name));
+        }
 
-        random_choice.as_str()
+        project.values.keys().map(String::as_str).collect()
     } else {
-        ""
+        vec![choose_values_name(
+            project,
+            project_name,
+            values_name,
+            random_values,
+        )?]
     };
 
     // Retrieve values
-    let mut values = if values_name.is_empty() {
-        if value_overrides.is_empty() {
-            return Err(anyhow!(
-                "Either a values name (--values) or the random flag (--random) has to be passed"
-            ));
-        }
-
-        Values {
-            data: HashMap::new(),
-            vars: HashMap::new(),
-        }
-    } else {
-        project.values.get(values_name).cloned().ok_or_else(|| {
-            anyhow!(
-                "No values named '{}' found in project '{}'",
+    let values: Vec<(&str, Values)> = values_names
+        .into_iter()
+        .map(|values_name| {
+            Ok((
                 values_name,
-                project_name,
-            )
-        })?
-    };
-
-    // Override values
-    for (value_name, value) in value_overrides {
-        values.data.insert(value_name.clone(), value.clone());
-    }
+                resolve_values(project, project_name, values_name, value_overrides)?,
+            ))
+        })
+        .collect::<Result<_>>()?;
 
     // Either take passed template or all
     let templates: Vec<_> = project
@@ -91,57 +77,220 @@ pub fn generate(
         };
     }
 
+    if all_values {
+        check_distinct_paths(&templates, &values, project_name)?;
+    }
+
     // Generate all templates
-    for template in templates {
-        generate_template(template, &values, values_name)?;
+    for (values_name, values) in &values {
+        for template in &templates {
+            generate_template(template, values, project_name, values_name)?;
+
+            if all_values {
+                println!(
+                    "Generated template '{}' with values '{}'",
+                    &template.name, values_name
+                );
+            } else {
+                println!("Generated template '{}'", &template.name);
+            }
+        }
+    }
 
-        println!("Generated template '{}'", &template.name);
+    Ok(())
+}
+
+/// Make sure no two values write the same output path, which happens if it does not depend on them
+fn check_distinct_paths(
+    templates: &[&Template],
+    values: &[(&str, Values)],
+    project_name: &str,
+) -> Result<()> {
+    let mut written_paths: HashMap<PathBuf, &str> = HashMap::new();
+
+    for (values_name, values) in values {
+        for template in templates {
+            let meta = TemplateMeta {
+                project: project_name,
+                values: values_name,
+                template: &template.name,
+                out: None,
+            };
+
+            for output in &template.out {
+                let (path, _) = render_paths(template, output, values, &meta)?;
+
+                if let Some(other_values_name) = written_paths.insert(path.clone(), values_name) {
+                    return Err(anyhow!(
+                        "Values '{}' and '{}' both write to '{}', make the output path of template '{}' depend on the values",
+                        other_values_name,
+                        values_name,
+                        path.display(),
+                        template.name
+                    ));
+                }
+            }
+        }
     }
 
     Ok(())
 }
 
-fn generate_template(template: &Template, values: &Values, values_name: &str) -> Result<()> {
-    // Expand 'repeat' statements
-    let mut repeated_template = template.contents.clone();
-    while REPEAT_REGEX.is_match(&repeated_template) {
-        repeated_template = expand_repeat_statement(&repeated_template, values, &template.name)?;
+pub fn choose_values_name<'a>(
+    project: &'a Project,
+    project_name: &str,
+    values_name: Option<&'a str>,
+    random_values: bool,
+) -> Result<&'a str> {
+    if let Some(name) = values_name {
+        Ok(name)
+    } else if random_values {
+        let random_choice = project
+            .values
+            .keys()
+            .choose(&mut rand::rng())
+            .ok_or_else(|| anyhow!("Project '{}' has no values", project_name))?;
+
+        Ok(random_choice.as_str())
+    } else {
+        Ok("")
     }
+}
 
-    // Fill template
-    let filled = fill_template(&repeated_template, &values.data, values_name)?;
+pub fn resolve_values(
+    project: &Project,
+    project_name: &str,
+    values_name: &str,
+    value_overrides: &[(String, String)],
+) -> Result<Values> {
+    let mut values = if values_name.is_empty() {
+        if value_overrides.is_empty() {
+            return Err(anyhow!(
+                "Either a values name (--values) or the random flag (--random) has to be passed"
+            ));
+        }
+
+        Values {
+            data: IndexMap::new(),
+            vars: IndexMap::new(),
+        }
+    } else {
+        project
+            .values
+            .get(values_name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "No values named '{}' found in project '{}'",
+                    values_name,
+                    project_name,
+                )
+            })?
+            .resolve()?
+    };
 
+    // Override values
+    for (value_name, value) in value_overrides {
+        values.set(value_name, value.clone())?;
+    }
+
+    Ok(values)
+}
+
+fn generate_template(
+    template: &Template,
+    values: &Values,
+    project_name: &str,
+    values_name: &str,
+) -> Result<()> {
     // Write template
-    for path in &template.out {
-        let contents = match template.mode {
-            TemplateMode::Replace => &filled,
-            TemplateMode::Append => &format!(
-                "{}{}",
-                clean_template(
-                    template.merge_path.as_ref().unwrap_or(path),
-                    &filled,
-                    &template.name
-                )?,
-                filled
-            ),
-            TemplateMode::Prepend => &format!(
-                "{}{}",
-                filled,
-                clean_template(
-                    template.merge_path.as_ref().unwrap_or(path),
-                    &filled,
+    for output in &template.out {
+        // Output paths may depend on values, but not on themselves
+        let path_meta = TemplateMeta {
+            project: project_name,
+            values: values_name,
+            template: &template.name,
+            out: None,
+        };
+        let (path, merge_path) = render_paths(template, output, values, &path_meta)?;
+
+        let meta = TemplateMeta {
+            project: project_name,
+            values: values_name,
+            template: &template.name,
+            out: Some(&path),
+        };
+
+        // Directory templates write every file below the output directory
+        if let TemplateSource::Directory { files, .. } = &template.source {
+            let rendered = render_directory(template, files, output, values, &meta)?;
+
+            for (file, (file_path, contents)) in files.iter().zip(rendered) {
+                write_template(
                     &template.name,
-                )?
-            ),
+                    &path.join(file_path),
+                    &contents,
+                    output.permissions.or(file.permissions),
+                    output.symlinks,
+                )?;
+            }
+
+            continue;
+        }
+
+        let filled = render_source(template, Some(output), values, &meta)?;
+
+        // Merging into existing files requires text
+        let text = || {
+            str::from_utf8(&filled).map_err(|_| {
+                anyhow!(
+                    "Template '{}' is not text and can only use the replace mode",
+                    template.name
+                )
+            })
+        };
+        let contents = match output.mode {
+            TemplateMode::Replace => filled,
+            TemplateMode::Append => {
+                let filled = text()?;
+
+                format!(
+                    "{}{}",
+                    clean_template(merge_path.as_ref().unwrap_or(&path), filled, &template.name)?,
+                    filled
+                )
+                .into_bytes()
+            }
+            TemplateMode::Prepend => {
+                let filled = text()?;
+
+                format!(
+                    "{}{}",
+                    filled,
+                    clean_template(merge_path.as_ref().unwrap_or(&path), filled, &template.name)?
+                )
+                .into_bytes()
+            }
         };
 
-        write_template(&template.name, path, contents)?;
+        write_template(
+            &template.name,
+            &path,
+            &contents,
+            output.permissions,
+            output.symlinks,
+        )?;
     }
 
     Ok(())
 }
 
-fn write_template(template_name: &str, path: &Path, contents: &str) -> Result<()> {
+fn write_template(
+    template_name: &str,
+    path: &Path,
+    contents: &[u8],
+    permissions: Option<u32>,
+    symlinks: SymlinkMode,
+) -> Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).with_context(|| {
             format!(
@@ -152,7 +301,23 @@ fn write_template(template_name: &str, path: &Path, contents: &str) -> Result<()
         })?;
     }
 
-    fs::write(path, contents).with_context(|| {
+    // Replace symlinks with a regular file instead of writing to their target
+    let is_symlink = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_symlink());
+    if is_symlink && matches!(symlinks, SymlinkMode::Replace) {
+        fs::remove_file(path).with_context(|| {
+            format!(
+                "Failed to remove symlink for template '{}' at '{}'",
+                &template_name,
+                path.display()
+            )
+        })?;
+    }
+
+    match permissions {
+        Some(permissions) => write_with_permissions(path, contents, permissions),
+        None => fs::write(path, contents),
+    }
+    .with_context(|| {
         format!(
             "Failed to write template '{}' to '{}'",
             &template_name,
@@ -161,6 +326,71 @@ fn write_template(template_name: &str, path: &Path, contents: &str) -> Result<()
     })
 }
 
+/// Write a temporary file with the permissions and rename it into place, so the contents are
+/// never readable with other permissions
+#[cfg(unix)]
+fn write_with_permissions(path: &Path, contents: &[u8], permissions: u32) -> io::Result<()> {
+    let path = resolve_symlinks(path)?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary_path =
+        path.with_file_name(format!(".{}.metemplate-{}", file_name, process::id()));
+
+    let result = write_new_file(&temporary_path, contents, permissions)
+        .and_then(|_| fs::rename(&temporary_path, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary_path);
+    }
+
+    result
+}
+
+#[cfg(unix)]
+fn write_new_file(path: &Path, contents: &[u8], permissions: u32) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(permissions)
+        .open(path)?;
+
+    // The umask may have cleared bits when creating the file
+    file.set_permissions(fs::Permissions::from_mode(permissions))?;
+    file.write_all(contents)
+}
+
+/// Follow symlinks to the file they point to, which might not exist yet
+#[cfg(unix)]
+fn resolve_symlinks(path: &Path) -> io::Result<PathBuf> {
+    let mut path = path.to_path_buf();
+
+    for _ in 0..40 {
+        if !fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_symlink()) {
+            return Ok(path);
+        }
+
+        let target = fs::read_link(&path)?;
+        path = match path.parent() {
+            Some(parent) => parent.join(target),
+            None => target,
+        };
+    }
+
+    Err(io::Error::other(format!(
+        "Too many levels of symlinks at '{}'",
+        path.display()
+    )))
+}
+
+// Only the read-only flag exists outside of unix
+#[cfg(not(unix))]
+fn write_with_permissions(path: &Path, contents: &[u8], permissions: u32) -> io::Result<()> {
+    fs::write(path, contents)?;
+
+    let mut file_permissions = fs::metadata(path)?.permissions();
+    file_permissions.set_readonly(permissions & 0o222 == 0);
+
+    fs::set_permissions(path, file_permissions)
+}
+
 fn clean_template(path: &Path, template: &str, template_name: &str) -> Result<String> {
     if !path.exists() {
         return Ok(String::new());
@@ -205,136 +435,3 @@ fn clean_template(path: &Path, template: &str, template_name: &str) -> Result<St
 
     Ok(result)
 }
-
-fn expand_repeat_statement(template: &str, values: &Values, template_name: &str) -> Result<String> {
-    let lines: Vec<&str> = template.lines().collect();
-
-    for (start_index, line) in lines.iter().enumerate() {
-        if let Some(captures) = REPEAT_REGEX.captures(line) {
-            let values_pool = match &captures[1] {
-                "values" => &values.data,
-                "vars" => &values.vars,
-                capture => {
-                    return Err(anyhow!(
-                        "Can only repeat over 'values' or 'vars': Got '{}'",
-                        capture
-                    ));
-                }
-            };
-
-            // Find 'endrepeat' statement
-            let end_index = lines[start_index + 1..]
-                .iter()
-                .position(|line2| ENDREPEAT_REGEX.is_match(line2))
-                .ok_or_else(|| anyhow!(
-                    "No endrepeat statement found after repeat statement in line '{}' in template '{}'",
-                    start_index + 1,
-                    template_name
-                ))? + start_index + 1;
-
-            // Check nested 'repeat' statement
-            if lines[start_index + 1..end_index]
-                .iter()
-                .any(|line2| REPEAT_REGEX.is_match(line2))
-            {
-                let nested_index = lines[start_index + 1..end_index]
-                    .iter()
-                    .position(|line2| REPEAT_REGEX.is_match(line2))
-                    .unwrap();
-
-                return Err(anyhow!(
-                    "Nested repeat statements not allowed. First in line '{}', second in line '{}'",
-                    start_index + 1,
-                    start_index + 1 + nested_index + 1
-                ));
-            }
-
-            let repeat_content = lines[start_index + 1..end_index].join("\n");
-
-            let mut insert_lines = String::new();
-            for (value_key, value_value) in values_pool {
-                let mut repeat_values = HashMap::with_capacity(2);
-                repeat_values.insert("key".to_string(), value_key.clone());
-                repeat_values.insert("value".to_string(), value_value.clone());
-
-                let filled = fill_template(&repeat_content, &repeat_values, "key,value")?;
-                insert_lines.push_str(&filled);
-                insert_lines.push('\n');
-            }
-
-            // Construct final template
-            let mut result = String::new();
-
-            // Lines before 'repeat' statement
-            result.push_str(&lines[..start_index].join("\n"));
-
-            // Repeated content
-            if !insert_lines.is_empty() {
-                result.push('\n');
-                result.push_str(insert_lines.trim_end());
-            }
-
-            // Lines after 'repeat' statement
-            if end_index + 1 < lines.len() {
-                result.push('\n');
-                result.push_str(&lines[end_index + 1..].join("\n"));
-            }
-
-            return Ok(result);
-        }
-    }
-
-    Ok(template.to_string())
-}
-
-fn fill_template(
-    template: &str,
-    value_pool: &HashMap<String, String>,
-    values_name: &str,
-) -> Result<String> {
-    // Fill out template
-    let mut missing_keys: Vec<String> = Vec::new();
-    let result = TEMPLATE_REGEX
-        .replace_all(template, |captures: &regex::Captures| {
-            let key = &captures[1];
-
-            if key.split_whitespace().count() != 1 {
-                // '{{' in a format string equals '{' so this returns '{{THE CAPTURE}}' essentially doing nothing
-                return format!("{{{{{}}}}}", &captures[1]);
-            }
-
-            let trimmed = key.trim_start_matches("-");
-            let dash_count = key.len() - trimmed.len();
-
-            match value_pool.get(trimmed) {
-                Some(value) => remove_prefix(value, dash_count).to_string(),
-                None => {
-                    missing_keys.push(trimmed.to_string());
-
-                    String::new()
-                }
-            }
-        })
-        .to_string();
-
-    // Report missing keys
-    if !missing_keys.is_empty() {
-        return Err(anyhow!(
-            "Could not find keys in values '{}': {}",
-            values_name,
-            missing_keys.join(", "),
-        ));
-    }
-
-    Ok(result)
-}
-
-fn remove_prefix(string: &str, amount: usize) -> &str {
-    let byte_index = string
-        .char_indices()
-        .nth(amount)
-        .map(|(index, _)| index)
-        .unwrap_or(string.len());
-
-    &string[byte_index..]
-}
@@ -0,0 +1,327 @@
+use crate::config::{Value, Values, read_data};
+use anyhow::{Context, Result, anyhow};
+use indexmap::IndexMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PALETTE_SIZE: usize = 16;
+
+/// Keys describing a scheme rather than its colors
+pub const SCHEME_METADATA: [&str; 2] = ["scheme", "author"];
+
+/// Load a base16 or base24 scheme file, or all scheme files in a directory, with their paths
+pub fn load_schemes(path: &Path) -> Result<Vec<(String, PathBuf, Values)>> {
+    if !path.is_dir() {
+        let (scheme_name, scheme_values) = load_scheme(path)?;
+
+        return Ok(vec![(scheme_name, path.to_path_buf(), scheme_values)]);
+    }
+
+    let mut scheme_paths: Vec<_> = fs::read_dir(path)
+        .with_context(|| {
+            format!(
+                "Failed to read scheme directory at path '{}'",
+                path.display()
+            )
+        })?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "yaml" || extension == "yml")
+        })
+        .collect();
+    scheme_paths.sort();
+
+    scheme_paths
+        .into_iter()
+        .map(|path| {
+            let (scheme_name, scheme_values) = load_scheme(&path)?;
+
+            Ok((scheme_name, path, scheme_values))
+        })
+        .collect()
+}
+
+fn load_scheme(path: &Path) -> Result<(String, Values)> {
+    let scheme_name = path.file_stem().unwrap().to_string_lossy().to_string();
+    let scheme: IndexMap<String, Value> = read_data(path)
+        .with_context(|| format!("Failed to read scheme file at path '{}'", path.display()))?;
+
+    // Current schemes nest colors under 'palette', legacy schemes declare them at the top level
+    let palette = match scheme.get("palette") {
+        Some(Value::Table(palette)) => palette,
+        _ => &scheme,
+    };
+
+    let mut data = IndexMap::new();
+    for (key, value) in palette {
+        let is_color_key = key.len() == 6
+            && key.starts_with("base")
+            && key[4..]
+                .chars()
+                .all(|character| character.is_ascii_hexdigit());
+
+        if let (true, Value::String(color)) = (is_color_key, value) {
+            // Legacy schemes omit the leading '#'
+            let color = format!("#{}", color.trim_start_matches('#'));
+            data.insert(key.clone(), Value::String(color));
+        }
+    }
+
+    if data.is_empty() {
+        return Err(anyhow!(
+            "No base16 or base24 colors found in scheme file at path '{}'",
+            path.display()
+        ));
+    }
+
+    // Name and author of the scheme
+    let [scheme_key, author_key] = SCHEME_METADATA;
+    if let Some(name) = scheme.get("name").or_else(|| scheme.get("scheme")) {
+        data.insert(scheme_key.to_string(), name.clone());
+    }
+    if let Some(author) = scheme.get("author") {
+        data.insert(author_key.to_string(), author.clone());
+    }
+
+    Ok((
+        scheme_name,
+        Values {
+            data,
+            vars: IndexMap::new(),
+        },
+    ))
+}
+
+/// Load a pywal or wallust style 'colors.json', flattening its 'special' and 'colors' sections
+pub fn load_pywal_colors(path: &Path) -> Result<Values> {
+    let colors: IndexMap<String, Value> = read_data(path)
+        .with_context(|| format!("Failed to read colors file at path '{}'", path.display()))?;
+
+    let mut data = IndexMap::new();
+    for (key, value) in colors {
+        match (key.as_str(), value) {
+            ("special" | "colors", Value::Table(section)) => data.extend(section),
+            (_, value) => {
+                data.insert(key, value);
+            }
+        }
+    }
+
+    Ok(Values {
+        data,
+        vars: IndexMap::new(),
+    })
+}
+
+/// Compute a pywal style palette from the dominant colors of an image
+pub fn extract_palette(path: &Path) -> Result<Values> {
+    let image = image::open(path)
+        .with_context(|| format!("Failed to read image at path '{}'", path.display()))?;
+
+    // Downscale to keep quantization cheap
+    let pixels: Vec<[u8; 3]> = image
+        .thumbnail(128, 128)
+        .to_rgb8()
+        .pixels()
+        .map(|pixel| pixel.0)
+        .collect();
+    if pixels.is_empty() {
+        return Err(anyhow!("Image at path '{}' is empty", path.display()));
+    }
+
+    // Drop near duplicates, which occur when large areas of one color are split
+    let mut colors: Vec<[u8; 3]> = Vec::with_capacity(PALETTE_SIZE);
+    for color in median_cut(pixels, PALETTE_SIZE) {
+        if colors.iter().all(|kept| distance(kept, &color) > 32.0) {
+            colors.push(color);
+        }
+    }
+    colors.sort_by(|a, b| luminance(a).total_cmp(&luminance(b)));
+
+    // Darkest and lightest colors become background and foreground
+    let background = colors[0];
+    let foreground = colors[colors.len() - 1];
+
+    // The most saturated remaining colors become accents, ordered by hue
+    let mut accents: Vec<[u8; 3]> = if colors.len() < 2 {
+        Vec::new()
+    } else {
+        colors[1..colors.len() - 1].to_vec()
+    };
+    accents.sort_by(|a, b| saturation(b).total_cmp(&saturation(a)));
+    accents.truncate(6);
+    accents.sort_by(|a, b| hue(a).total_cmp(&hue(b)));
+    if accents.is_empty() {
+        accents.push(mix(background, foreground, 0.5));
+    }
+
+    let mut palette = Vec::with_capacity(PALETTE_SIZE);
+    palette.push(background);
+    palette.extend((0..6).map(|index| accents[index % accents.len()]));
+    palette.push(foreground);
+    palette.push(mix(background, foreground, 0.25));
+    palette.extend((0..6).map(|index| mix(accents[index % accents.len()], [255; 3], 0.2)));
+    palette.push(foreground);
+
+    let mut data = IndexMap::new();
+    data.insert(
+        "wallpaper".to_string(),
+        Value::String(path.display().to_string()),
+    );
+    for (key, color) in [
+        ("background", background),
+        ("foreground", foreground),
+        ("cursor", foreground),
+    ] {
+        data.insert(key.to_string(), Value::String(to_hex(color)));
+    }
+    for (index, color) in palette.into_iter().enumerate() {
+        data.insert(format!("color{}", index), Value::String(to_hex(color)));
+    }
+
+    Ok(Values {
+        data,
+        vars: IndexMap::new(),
+    })
+}
+
+/// Reduce pixels to at most 'count' colors by repeatedly splitting the widest bucket
+fn median_cut(pixels: Vec<[u8; 3]>, count: usize) -> Vec<[u8; 3]> {
+    let mut buckets = vec![pixels];
+
+    while buckets.len() < count {
+        let widest = buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, bucket)| bucket.len() > 1)
+            .map(|(index, bucket)| {
+                let (channel, range) = (0..3)
+                    .map(|channel| {
+                        let (min, max) =
+                            bucket.iter().fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+                                (min.min(pixel[channel]), max.max(pixel[channel]))
+                            });
+
+                        (channel, max - min)
+                    })
+                    .max_by_key(|(_, range)| *range)
+                    .unwrap();
+
+                (index, channel, range)
+            })
+            .filter(|(_, _, range)| *range > 0)
+            .max_by_key(|(_, _, range)| *range);
+
+        let Some((index, channel, _)) = widest else {
+            break;
+        };
+
+        let mut bucket = buckets.swap_remove(index);
+        bucket.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper = bucket.split_off(bucket.len() / 2);
+        buckets.push(bucket);
+        buckets.push(upper);
+    }
+
+    buckets
+        .iter()
+        .map(|bucket| {
+            let sum = bucket.iter().fold([0u64; 3], |sum, pixel| {
+                [
+                    sum[0] + u64::from(pixel[0]),
+                    sum[1] + u64::from(pixel[1]),
+                    sum[2] + u64::from(pixel[2]),
+                ]
+            });
+            let length = bucket.len() as u64;
+
+            [
+                (sum[0] / length) as u8,
+                (sum[1] / length) as u8,
+                (sum[2] / length) as u8,
+            ]
+        })
+        .collect()
+}
+
+fn distance(a: &[u8; 3], b: &[u8; 3]) -> f64 {
+    (0..3)
+        .map(|channel| (f64::from(a[channel]) - f64::from(b[channel])).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+fn luminance(color: &[u8; 3]) -> f64 {
+    0.2126 * f64::from(color[0]) + 0.7152 * f64::from(color[1]) + 0.0722 * f64::from(color[2])
+}
+
+fn saturation(color: &[u8; 3]) -> f64 {
+    let max = f64::from(*color.iter().max().unwrap());
+    let min = f64::from(*color.iter().min().unwrap());
+
+    if max == 0.0 { 0.0 } else { (max - min) / max }
+}
+
+fn hue(color: &[u8; 3]) -> f64 {
+    let [red, green, blue] = color.map(f64::from);
+    let max = red.max(green).max(blue);
+    let delta = max - red.min(green).min(blue);
+
+    if delta == 0.0 {
+        0.0
+    } else if max == red {
+        60.0 * ((green - blue) / delta).rem_euclid(6.0)
+    } else if max == green {
+        60.0 * ((blue - red) / delta + 2.0)
+    } else {
+        60.0 * ((red - green) / delta + 4.0)
+    }
+}
+
+fn mix(a: [u8; 3], b: [u8; 3], amount: f64) -> [u8; 3] {
+    [0, 1, 2].map(|channel| {
+        (f64::from(a[channel]) * (1.0 - amount) + f64::from(b[channel]) * amount).round() as u8
+    })
+}
+
+fn to_hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use std::env;
+
+    #[test]
+    fn extract_palette_from_single_color_image() {
+        let path = env::temp_dir().join(format!("metemplate-solid-{}.png", std::process::id()));
+        RgbImage::from_pixel(4, 4, Rgb([10, 20, 30]))
+            .save(&path)
+            .unwrap();
+
+        let values = extract_palette(&path);
+        fs::remove_file(&path).unwrap();
+        let values = values.unwrap();
+
+        for key in ["background", "foreground", "color0", "color1", "color15"] {
+            assert!(
+                matches!(values.data.get(key), Some(Value::String(color)) if color == "#0a141e"),
+                "{} is not the image color",
+                key
+            );
+        }
+        assert_eq!(
+            values
+                .data
+                .keys()
+                .filter(|key| key.starts_with("color"))
+                .count(),
+            16
+        );
+    }
+}
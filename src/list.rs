@@ -1,25 +1,93 @@
-use crate::config::Config;
+use crate::cli::ListFormat;
+use crate::config::{
+    Config, OutputFormat, Project, SymlinkMode, Template, TemplateMode, TemplateSource,
+};
 use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::path::Path;
 
-pub fn list(project_name: Option<&str>, no_values: bool, config: &Config) -> Result<()> {
-    let projects = config.projects.iter().filter(|(name, _)| {
-        project_name
-            .as_ref()
-            .is_none_or(|project_name| project_name == *name)
-    });
+#[derive(Serialize)]
+struct ProjectEntry<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    values: Option<Vec<&'a str>>,
+    templates: Vec<TemplateEntry<'a>>,
+}
+#[derive(Serialize)]
+struct TemplateEntry<'a> {
+    name: &'a str,
+    file: Option<&'a Path>,
+    format: Option<OutputFormat>,
+    out: Vec<OutputEntry<'a>>,
+}
+#[derive(Serialize)]
+struct OutputEntry<'a> {
+    path: &'a Path,
+    mode: TemplateMode,
+    merge_path: Option<&'a Path>,
+    permissions: Option<String>,
+    symlinks: SymlinkMode,
+}
 
-    let mut found = false;
-    for (index, (project_name, project)) in projects.enumerate() {
-        found = true;
+pub fn list(
+    project_name: Option<&str>,
+    no_values: bool,
+    templates: bool,
+    format: ListFormat,
+    config: &Config,
+) -> Result<()> {
+    let projects: Vec<_> = config
+        .projects
+        .iter()
+        .filter(|(name, _)| {
+            project_name
+                .as_ref()
+                .is_none_or(|project_name| project_name == *name)
+        })
+        .collect();
 
-        if !no_values && index > 0 {
+    if projects.is_empty() {
+        return match project_name {
+            Some(name) => Err(anyhow!("No project named '{}' found", name)),
+            None => Err(anyhow!("No projects found")),
+        };
+    }
+
+    match format {
+        ListFormat::Tree => print_tree(&projects, no_values, templates),
+        ListFormat::Plain => print_plain(&projects, no_values, templates),
+        ListFormat::Json => print_json(&projects, no_values)?,
+    }
+
+    Ok(())
+}
+
+fn print_tree(projects: &[(&String, &Project)], no_values: bool, templates: bool) {
+    let print_children = templates || !no_values;
+
+    for (index, (project_name, project)) in projects.iter().enumerate() {
+        if print_children && index > 0 {
             println!();
         }
 
         // Print project name
         println!("{}", project_name);
 
-        if !no_values {
+        if templates {
+            // Print templates and their outputs in a tree shape
+            let mut templates_iter = project.templates.iter().peekable();
+
+            while let Some(template) = templates_iter.next() {
+                let (prefix, indent) = if templates_iter.peek().is_some() {
+                    ("├", "│")
+                } else {
+                    ("└", " ")
+                };
+
+                println!("  {}─ {}", prefix, template.name);
+                print_template_details(template, indent);
+            }
+        } else if !no_values {
             // Print values in a tree shape
             let mut values_names_iter = project.values.keys().peekable();
 
@@ -34,13 +102,112 @@ pub fn list(project_name: Option<&str>, no_values: bool, config: &Config) -> Res
             }
         }
     }
+}
 
-    if !found {
-        return match project_name {
-            Some(ref name) => Err(anyhow!("No project named '{}' found", name)),
-            None => Err(anyhow!("No projects found")),
+fn print_template_details(template: &Template, indent: &str) {
+    let mut details = vec![match &template.source {
+        TemplateSource::File { path, .. } => ("file", path.display().to_string()),
+        TemplateSource::Directory { path, .. } => ("directory", path.display().to_string()),
+        TemplateSource::Format(format) => ("format", format.to_string()),
+    }];
+    for output in &template.out {
+        details.push((
+            "out",
+            format!("{} ({})", output.path.display(), output.mode),
+        ));
+        if let Some(merge_path) = &output.merge_path {
+            details.push(("merge", merge_path.display().to_string()));
+        }
+        if let Some(permissions) = output.permissions {
+            details.push(("permissions", format!("{:04o}", permissions)));
+        }
+        if let SymlinkMode::Replace = output.symlinks {
+            details.push(("symlinks", output.symlinks.to_string()));
+        }
+    }
+
+    let mut details_iter = details.iter().peekable();
+    while let Some((label, value)) = details_iter.next() {
+        let prefix = if details_iter.peek().is_some() {
+            "├"
+        } else {
+            "└"
         };
+
+        println!("  {}   {}─ {}: {}", indent, prefix, label, value);
+    }
+}
+
+fn print_plain(projects: &[(&String, &Project)], no_values: bool, templates: bool) {
+    for (project_name, project) in projects {
+        // Print one 'project<TAB>template<TAB>mode<TAB>out' line per output path
+        if templates {
+            for template in &project.templates {
+                for output in &template.out {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        project_name,
+                        template.name,
+                        output.mode,
+                        output.path.display()
+                    );
+                }
+            }
+
+            continue;
+        }
+
+        // Print one 'project<TAB>values' pair per line, or only the project without values
+        if no_values || project.values.is_empty() {
+            println!("{}", project_name);
+            continue;
+        }
+
+        for values_name in project.values.keys() {
+            println!("{}\t{}", project_name, values_name);
+        }
     }
+}
+
+fn print_json(projects: &[(&String, &Project)], no_values: bool) -> Result<()> {
+    let entries: Vec<_> = projects
+        .iter()
+        .map(|(project_name, project)| ProjectEntry {
+            name: project_name,
+            values: (!no_values).then(|| project.values.keys().map(String::as_str).collect()),
+            templates: project
+                .templates
+                .iter()
+                .map(|template| TemplateEntry {
+                    name: &template.name,
+                    file: match &template.source {
+                        TemplateSource::File { path, .. }
+                        | TemplateSource::Directory { path, .. } => Some(path),
+                        TemplateSource::Format(_) => None,
+                    },
+                    format: match &template.source {
+                        TemplateSource::File { .. } | TemplateSource::Directory { .. } => None,
+                        TemplateSource::Format(format) => Some(*format),
+                    },
+                    out: template
+                        .out
+                        .iter()
+                        .map(|output| OutputEntry {
+                            path: &output.path,
+                            mode: output.mode,
+                            merge_path: output.merge_path.as_deref(),
+                            permissions: output
+                                .permissions
+                                .map(|permissions| format!("{:04o}", permissions)),
+                            symlinks: output.symlinks,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    println!("{}", serde_json::to_string_pretty(&entries)?);
 
     Ok(())
 }
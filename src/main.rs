@@ -1,12 +1,20 @@
 mod cli;
 mod config;
+mod format;
 mod generate;
+mod import;
 mod list;
+mod recolor;
+mod render;
+mod show;
+mod template;
 
 use crate::cli::{Cli, Commands};
 use crate::config::Config;
 use crate::generate::generate;
 use crate::list::list;
+use crate::render::render;
+use crate::show::show;
 use anyhow::Result;
 use clap::Parser;
 
@@ -15,21 +23,48 @@ fn run() -> Result<()> {
     let config = Config::parse(&cli)?;
 
     match cli.command {
-        Commands::List { project, no_values } => list(project.as_deref(), no_values, &config),
+        Commands::List {
+            project,
+            no_values,
+            templates,
+            format,
+        } => list(project.as_deref(), no_values, templates, format, &config),
         Commands::Generate {
             project,
             values,
             value_overrides,
             random, // inferred in generate() by values being None
+            all_values,
             template,
         } => generate(
             &project,
             values.as_deref(),
             &value_overrides,
             random,
+            all_values,
             template.as_deref(),
             &config,
         ),
+        Commands::Render {
+            project,
+            template,
+            values,
+            value_overrides,
+            random,
+        } => render(
+            &project,
+            &template,
+            values.as_deref(),
+            &value_overrides,
+            random,
+            &config,
+        ),
+        Commands::Show {
+            project,
+            values,
+            value_overrides,
+            format,
+        } => show(&project, &values, &value_overrides, format, &config),
     }
 }
 
@@ -0,0 +1,99 @@
+use anyhow::{Context, Result, anyhow};
+use image::ImageFormat;
+use regex::Regex;
+use std::io::Cursor;
+use std::sync::LazyLock;
+
+static HEX_COLOR_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"#(?:[0-9a-fA-F]{6}|[0-9a-fA-F]{3})\b").unwrap());
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// A source color and the color replacing it
+pub struct ColorMapping {
+    pub from: [u8; 3],
+    pub to: String,
+}
+
+/// Parse a '#rrggbb' or '#rgb' color
+pub fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if !hex.chars().all(|character| character.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    match hex.len() {
+        6 => Some([0, 2, 4].map(|index| u8::from_str_radix(&hex[index..index + 2], 16).unwrap())),
+        3 => Some([0, 1, 2].map(|index| u8::from_str_radix(&hex[index..=index], 16).unwrap() * 17)),
+        _ => None,
+    }
+}
+
+/// Replace hex colors in text, matching them regardless of case and length
+pub fn recolor_text(text: &str, mappings: &[ColorMapping]) -> String {
+    HEX_COLOR_REGEX
+        .replace_all(text, |captures: &regex::Captures| {
+            let color = parse_color(&captures[0]);
+
+            match mappings.iter().find(|mapping| Some(mapping.from) == color) {
+                Some(mapping) => mapping.to.clone(),
+                None => captures[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+/// Replace exactly matching pixel colors of a PNG image, other binary files are left unchanged
+pub fn recolor_binary(bytes: &[u8], mappings: &[ColorMapping]) -> Result<Vec<u8>> {
+    if mappings.is_empty() || !bytes.starts_with(PNG_SIGNATURE) {
+        return Ok(bytes.to_vec());
+    }
+
+    let mappings = mappings
+        .iter()
+        .map(|mapping| {
+            let to = parse_color(&mapping.to).ok_or_else(|| {
+                anyhow!(
+                    "Value '{}' can not be used to recolor a PNG image, it is not a hex color",
+                    mapping.to
+                )
+            })?;
+
+            Ok((mapping.from, to))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut image = image::load_from_memory_with_format(bytes, ImageFormat::Png)
+        .context("Failed to decode PNG image")?
+        .to_rgba8();
+    for pixel in image.pixels_mut() {
+        let [red, green, blue, alpha] = pixel.0;
+
+        if let Some((_, to)) = mappings
+            .iter()
+            .find(|(from, _)| *from == [red, green, blue])
+        {
+            pixel.0 = [to[0], to[1], to[2], alpha];
+        }
+    }
+
+    let mut recolored = Cursor::new(Vec::new());
+    image
+        .write_to(&mut recolored, ImageFormat::Png)
+        .context("Failed to encode PNG image")?;
+
+    Ok(recolored.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_without_mappings_is_unchanged() {
+        // Not decodable past the signature, so re-encoding would fail
+        let bytes = [PNG_SIGNATURE, b"not really a png"].concat();
+
+        assert_eq!(recolor_binary(&bytes, &[]).unwrap(), bytes);
+    }
+}
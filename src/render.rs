@@ -0,0 +1,70 @@
+use crate::config::Config;
+use crate::generate::{choose_values_name, resolve_values};
+use crate::template::{TemplateMeta, render_source, render_template};
+use anyhow::{Context, Result, anyhow};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+pub fn render(
+    project_name: &str,
+    template_source: &str,
+    values_name: Option<&str>,
+    value_overrides: &[(String, String)],
+    random_values: bool,
+    config: &Config,
+) -> Result<()> {
+    // Retrieve project
+    let project = config
+        .projects
+        .get(project_name)
+        .ok_or_else(|| anyhow!("No project named '{}' found", project_name))?;
+
+    // Retrieve values
+    let values_name = choose_values_name(project, project_name, values_name, random_values)?;
+    let values = resolve_values(project, project_name, values_name, value_overrides)?;
+
+    let meta = TemplateMeta {
+        project: project_name,
+        values: values_name,
+        template: template_source,
+        out: None,
+    };
+
+    // Render project template
+    if let Some(template) = project
+        .templates
+        .iter()
+        .find(|template| template.name == template_source)
+    {
+        io::stdout()
+            .write_all(&render_source(template, None, &values, &meta)?)
+            .context("Failed to write template to stdout")?;
+
+        return Ok(());
+    }
+
+    // Read template from stdin or an arbitrary file
+    let contents = if template_source == "-" {
+        let mut contents = String::new();
+        io::stdin()
+            .read_to_string(&mut contents)
+            .context("Failed to read template from stdin")?;
+
+        contents
+    } else if Path::new(template_source).is_file() {
+        fs::read_to_string(template_source).with_context(|| {
+            format!("Failed to read template file at path '{}'", template_source)
+        })?
+    } else {
+        return Err(anyhow!(
+            "No template named '{}' found in project '{}'",
+            template_source,
+            project_name
+        ));
+    };
+
+    print!("{}", render_template(&contents, &values, &meta)?);
+
+    Ok(())
+}
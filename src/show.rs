@@ -0,0 +1,30 @@
+use crate::cli::ShowFormat;
+use crate::config::Config;
+use crate::generate::resolve_values;
+use anyhow::{Result, anyhow};
+
+pub fn show(
+    project_name: &str,
+    values_name: &str,
+    value_overrides: &[(String, String)],
+    format: ShowFormat,
+    config: &Config,
+) -> Result<()> {
+    // Retrieve project
+    let project = config
+        .projects
+        .get(project_name)
+        .ok_or_else(|| anyhow!("No project named '{}' found", project_name))?;
+
+    // Resolve values
+    let values = resolve_values(project, project_name, values_name, value_overrides)?;
+
+    let output = match format {
+        ShowFormat::Toml => toml::to_string(&values.data)?,
+        ShowFormat::Json => serde_json::to_string_pretty(&values.data)?,
+    };
+
+    println!("{}", output.trim_end());
+
+    Ok(())
+}
@@ -0,0 +1,847 @@
+use crate::config::{
+    FileContents, Template, TemplateFile, TemplateOutput, TemplateSource, Value, Values, ValuesMap,
+    expand_path, lookup,
+};
+use crate::format::format_values;
+use crate::recolor::{ColorMapping, parse_color, recolor_binary, recolor_text};
+use anyhow::{Context, Result, anyhow};
+use indexmap::IndexMap;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static TEMPLATE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*([^\s]+?)\s*\}\}").unwrap());
+static STATEMENT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<\{(-?)\s*(.*?)\s*(-?)\}>").unwrap());
+static REPEAT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^repeat\s+([^\s]+)(?:\s+as\s+([^\s,]+)\s*,\s*([^\s]+))?(?:\s+sort\s+by\s+(key|value))?(\s+reverse)?$",
+    )
+    .unwrap()
+});
+static IF_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^if\s+(not\s+)?([^\s]+)$").unwrap());
+
+static DATE: LazyLock<String> = LazyLock::new(current_date);
+static HOSTNAME: LazyLock<Option<String>> = LazyLock::new(current_hostname);
+
+/// Context exposed to templates under the reserved 'meta.' namespace
+pub struct TemplateMeta<'a> {
+    pub project: &'a str,
+    pub values: &'a str,
+    pub template: &'a str,
+    pub out: Option<&'a Path>,
+}
+
+impl TemplateMeta<'_> {
+    fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "project" => Some(self.project.to_string()),
+            "values" => Some(self.values.to_string()),
+            "template" => Some(self.template.to_string()),
+            // Rendering to stdout has no output path
+            "out" => Some(
+                self.out
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_default(),
+            ),
+            "date" => Some(DATE.clone()),
+            "hostname" => HOSTNAME.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// Block statements written as '<{ statement }>', '<{-' and '-}>' trim surrounding whitespace
+///
+/// - '<{ repeat PATH [as KEY, VALUE] [sort by key|value] [reverse] }>' ... '<{ endrepeat }>'
+///   renders its body once per entry of 'values', 'vars' or a table or array at PATH, exposing
+///   'index', 'first' and 'last' alongside the entry
+/// - '<{ if [not] KEY }>' ... '<{ endif }>' renders its body if KEY is truthy (see 'is_truthy'),
+///   which is what makes 'first' and 'last' usable, e.g. '<{ if not last }>,<{ endif }>'
+enum Statement<'a> {
+    Repeat {
+        path: &'a str,
+        key_name: &'a str,
+        value_name: &'a str,
+        sort_by: Option<&'a str>,
+        reverse: bool,
+    },
+    EndRepeat,
+    If {
+        negated: bool,
+        key: &'a str,
+    },
+    EndIf,
+}
+
+enum Node<'a> {
+    Text(&'a str),
+    Block {
+        statement: Statement<'a>,
+        body: Vec<Node<'a>>,
+    },
+}
+
+/// Render a project template from its file or by formatting the values
+pub fn render_source(
+    template: &Template,
+    output: Option<&TemplateOutput>,
+    values: &Values,
+    meta: &TemplateMeta,
+) -> Result<Vec<u8>> {
+    let values = template_values(template, output, values)?;
+
+    match &template.source {
+        TemplateSource::File { contents, .. } => render_contents(template, contents, &values, meta),
+        TemplateSource::Directory { .. } => Err(anyhow!(
+            "Directory template '{}' can not be rendered to a single file",
+            template.name
+        )),
+        TemplateSource::Format(format) => Ok(format_values(&values.data, *format)?.into_bytes()),
+    }
+}
+
+/// Render all files of a directory template, filling their paths relative to the output directory
+pub fn render_directory(
+    template: &Template,
+    files: &[TemplateFile],
+    output: &TemplateOutput,
+    values: &Values,
+    meta: &TemplateMeta,
+) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let values = template_values(template, Some(output), values)?;
+
+    files
+        .iter()
+        .map(|file| {
+            let path = fill_template(
+                &file.path.to_string_lossy(),
+                &values.data,
+                meta.values,
+                meta,
+            )?;
+
+            let contents = render_contents(template, &file.contents, &values, meta)?;
+
+            Ok((PathBuf::from(path), contents))
+        })
+        .collect()
+}
+
+/// Render a text file or copy a binary file verbatim, replacing the colors of both if recoloring
+fn render_contents(
+    template: &Template,
+    contents: &FileContents,
+    values: &Values,
+    meta: &TemplateMeta,
+) -> Result<Vec<u8>> {
+    let mappings = color_mappings(template, &values.data)?;
+
+    // Binary files can not contain placeholders
+    match contents {
+        // Only colors written in the template are recolored, not colors filled in from values
+        FileContents::Text(contents) => {
+            let recolored = recolor_text(contents, &mappings);
+
+            Ok(render_template(&recolored, values, meta)?.into_bytes())
+        }
+        FileContents::Binary(contents) if mappings.is_empty() => Ok(contents.clone()),
+        FileContents::Binary(contents) => recolor_binary(contents, &mappings)
+            .with_context(|| format!("Failed to recolor template '{}'", template.name)),
+    }
+}
+
+/// Colors to replace and the values replacing them
+fn color_mappings(
+    template: &Template,
+    value_pool: &IndexMap<String, Value>,
+) -> Result<Vec<ColorMapping>> {
+    template
+        .recolor
+        .iter()
+        .map(|(color, key)| match lookup(value_pool, key) {
+            Some(Value::String(value)) => Ok(ColorMapping {
+                from: parse_color(color).unwrap(),
+                to: value.clone(),
+            }),
+            _ => Err(anyhow!(
+                "Value '{}' to recolor '{}' in template '{}' not found",
+                key,
+                color,
+                template.name
+            )),
+        })
+        .collect()
+}
+
+/// Fill the output path and merge file path of a template output, then expand them
+pub fn render_paths(
+    template: &Template,
+    output: &TemplateOutput,
+    values: &Values,
+    meta: &TemplateMeta,
+) -> Result<(PathBuf, Option<PathBuf>)> {
+    let values = template_values(template, Some(output), values)?;
+    let home_dir = dirs::home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
+    let render_path = |path: &Path| {
+        let filled = fill_template(&path.to_string_lossy(), &values.data, meta.values, meta)?;
+
+        expand_path(Path::new(&filled), &home_dir, &output.project_path)
+    };
+
+    Ok((
+        render_path(&output.path)?,
+        output.merge_path.as_deref().map(render_path).transpose()?,
+    ))
+}
+
+/// Values passed to a template after mapping them and adding template and output vars
+fn template_values(
+    template: &Template,
+    output: Option<&TemplateOutput>,
+    values: &Values,
+) -> Result<Values> {
+    let mut values = match &template.values_map {
+        Some(values_map) => map_values(values, values_map, &template.name)?,
+        None => values.clone(),
+    };
+
+    // Output vars take precedence over template vars, which take precedence over values
+    let output_vars = output.into_iter().flat_map(|output| &output.vars);
+    for (key, value) in template.vars.iter().chain(output_vars) {
+        values.data.insert(key.clone(), value.clone());
+    }
+
+    Ok(values)
+}
+
+/// Select or rename the values passed to a template
+fn map_values(values: &Values, values_map: &ValuesMap, template_name: &str) -> Result<Values> {
+    let mapped = |key: &str, source: &str| {
+        lookup(&values.data, source).cloned().ok_or_else(|| {
+            anyhow!(
+                "Value '{}' mapped to '{}' in template '{}' not found",
+                source,
+                key,
+                template_name
+            )
+        })
+    };
+
+    let data = match values_map {
+        ValuesMap::Select(keys) => keys
+            .iter()
+            .map(|key| {
+                let value = lookup(&values.data, key).cloned().ok_or_else(|| {
+                    anyhow!(
+                        "Value '{}' selected in template '{}' not found",
+                        key,
+                        template_name
+                    )
+                })?;
+
+                Ok((key.clone(), value))
+            })
+            .collect::<Result<_>>()?,
+        // Renamed keys come first, followed by all values that were not renamed
+        ValuesMap::Rename(renames) => {
+            let mut data: IndexMap<String, Value> = renames
+                .iter()
+                .map(|(key, source)| Ok((key.clone(), mapped(key, source)?)))
+                .collect::<Result<_>>()?;
+            for (key, value) in &values.data {
+                if !renames.values().any(|source| source == key) {
+                    data.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+            }
+
+            data
+        }
+    };
+
+    Ok(Values {
+        data,
+        vars: values.vars.clone(),
+    })
+}
+
+pub fn render_template(contents: &str, values: &Values, meta: &TemplateMeta) -> Result<String> {
+    let nodes = parse(contents, meta.template)?;
+
+    render_nodes(&nodes, &values.data, meta.values, values, meta)
+}
+
+fn parse<'a>(template: &'a str, template_name: &str) -> Result<Vec<Node<'a>>> {
+    let mut nodes = Vec::new();
+    let mut open_blocks: Vec<(Statement, usize, Vec<Node>)> = Vec::new();
+    let mut position = 0;
+    let mut trim_next = false;
+
+    for captures in STATEMENT_REGEX.captures_iter(template) {
+        let tag = captures.get(0).unwrap();
+        let line = template[..tag.start()].matches('\n').count() + 1;
+
+        // Unknown statements are left as text
+        let Some(statement) =
+            parse_statement(captures.get(2).unwrap().as_str(), line, template_name)?
+        else {
+            continue;
+        };
+
+        // Statements on their own line remove the whole line
+        let (mut start, mut end) = (tag.start(), tag.end());
+        let line_start = template[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = template[end..]
+            .find('\n')
+            .map_or(template.len(), |index| end + index + 1);
+        if line_start >= position
+            && template[line_start..start].trim().is_empty()
+            && template[end..line_end].trim().is_empty()
+        {
+            start = line_start;
+            end = line_end;
+        }
+
+        // '<{-' and '-}>' remove whitespace before and after the statement
+        let mut text = &template[position..start];
+        if trim_next {
+            text = text.trim_start();
+        }
+        if !captures[1].is_empty() {
+            text = text.trim_end();
+        }
+        trim_next = !captures[3].is_empty();
+        position = end;
+
+        let body = match open_blocks.last_mut() {
+            Some((_, _, body)) => body,
+            None => &mut nodes,
+        };
+        body.push(Node::Text(text));
+
+        match statement {
+            Statement::Repeat { .. } | Statement::If { .. } => {
+                open_blocks.push((statement, line, Vec::new()));
+            }
+            Statement::EndRepeat | Statement::EndIf => {
+                let open_statement = match open_blocks.pop() {
+                    Some((open_statement @ Statement::Repeat { .. }, _, body))
+                        if matches!(statement, Statement::EndRepeat) =>
+                    {
+                        Node::Block {
+                            statement: open_statement,
+                            body,
+                        }
+                    }
+                    Some((open_statement @ Statement::If { .. }, _, body))
+                        if matches!(statement, Statement::EndIf) =>
+                    {
+                        Node::Block {
+                            statement: open_statement,
+                            body,
+                        }
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "Closing statement without matching opening statement in line '{}' in template '{}'",
+                            line,
+                            template_name
+                        ));
+                    }
+                };
+
+                let parent = match open_blocks.last_mut() {
+                    Some((_, _, parent)) => parent,
+                    None => &mut nodes,
+                };
+                parent.push(open_statement);
+            }
+        }
+    }
+
+    if let Some((statement, line, _)) = open_blocks.last() {
+        let (open, close) = match statement {
+            Statement::Repeat { .. } => ("repeat", "endrepeat"),
+            _ => ("if", "endif"),
+        };
+
+        return Err(anyhow!(
+            "No {} statement found after {} statement in line '{}' in template '{}'",
+            close,
+            open,
+            line,
+            template_name
+        ));
+    }
+
+    // Text after last statement
+    let text = &template[position..];
+    nodes.push(Node::Text(if trim_next { text.trim_start() } else { text }));
+
+    Ok(nodes)
+}
+
+fn parse_statement<'a>(
+    statement: &'a str,
+    line: usize,
+    template_name: &str,
+) -> Result<Option<Statement<'a>>> {
+    let parsed = match statement.split_whitespace().next() {
+        Some("endrepeat") if statement == "endrepeat" => Some(Statement::EndRepeat),
+        Some("endif") if statement == "endif" => Some(Statement::EndIf),
+        Some("repeat") => REPEAT_REGEX
+            .captures(statement)
+            .map(|captures| Statement::Repeat {
+                path: captures.get(1).unwrap().as_str(),
+                key_name: captures.get(2).map_or("key", |name| name.as_str()),
+                value_name: captures.get(3).map_or("value", |name| name.as_str()),
+                sort_by: captures.get(4).map(|sort_by| sort_by.as_str()),
+                reverse: captures.get(5).is_some(),
+            }),
+        Some("if") => IF_REGEX.captures(statement).map(|captures| Statement::If {
+            negated: captures.get(1).is_some(),
+            key: captures.get(2).unwrap().as_str(),
+        }),
+        _ => return Ok(None),
+    };
+
+    parsed.map(Some).ok_or_else(|| {
+        anyhow!(
+            "Invalid statement '{}' in line '{}' in template '{}'",
+            statement,
+            line,
+            template_name
+        )
+    })
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    value_pool: &IndexMap<String, Value>,
+    values_name: &str,
+    values: &Values,
+    meta: &TemplateMeta,
+) -> Result<String> {
+    let mut result = String::new();
+
+    for node in nodes {
+        match node {
+            Node::Text(text) => {
+                result.push_str(&fill_template(text, value_pool, values_name, meta)?);
+            }
+            Node::Block {
+                statement:
+                    Statement::Repeat {
+                        path,
+                        key_name,
+                        value_name,
+                        sort_by,
+                        reverse,
+                    },
+                body,
+            } => {
+                // Items are in file order unless sorted
+                let mut entries = repeat_entries(path, value_pool, values)?;
+                match *sort_by {
+                    Some("key") => entries.sort_by(|(a, _), (b, _)| compare_text(a, b)),
+                    Some("value") => entries.sort_by(|(_, a), (_, b)| match (a, b) {
+                        (Value::String(a), Value::String(b)) => compare_text(a, b),
+                        _ => Ordering::Equal,
+                    }),
+                    _ => {}
+                }
+                if *reverse {
+                    entries.reverse();
+                }
+
+                let count = entries.len();
+                for (index, (value_key, value_value)) in entries.into_iter().enumerate() {
+                    // Loop metadata and items shadow values and items of enclosing 'repeat'
+                    // statements, items are inserted last so names given with 'as' win
+                    let mut repeat_values = value_pool.clone();
+                    repeat_values.insert("index".to_string(), Value::String(index.to_string()));
+                    repeat_values
+                        .insert("first".to_string(), Value::String((index == 0).to_string()));
+                    repeat_values.insert(
+                        "last".to_string(),
+                        Value::String((index + 1 == count).to_string()),
+                    );
+                    repeat_values.insert(key_name.to_string(), Value::String(value_key));
+                    repeat_values.insert(value_name.to_string(), value_value);
+
+                    result.push_str(&render_nodes(
+                        body,
+                        &repeat_values,
+                        &format!("{},{}", key_name, value_name),
+                        values,
+                        meta,
+                    )?);
+                }
+            }
+            Node::Block {
+                statement: Statement::If { negated, key },
+                body,
+            } => {
+                let condition =
+                    resolve_key(key, value_pool, meta).is_some_and(|value| is_truthy(&value));
+
+                if condition != *negated {
+                    result.push_str(&render_nodes(body, value_pool, values_name, values, meta)?);
+                }
+            }
+            Node::Block { .. } => unreachable!("Closing statements are never stored as blocks"),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Missing values, empty strings, 'false', '0' and empty collections are false, everything else
+/// is true
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::String(value) => !matches!(value.as_str(), "" | "false" | "0"),
+        Value::Array(items) => !items.is_empty(),
+        Value::Table(table) => !table.is_empty(),
+    }
+}
+
+fn repeat_entries(
+    path: &str,
+    value_pool: &IndexMap<String, Value>,
+    values: &Values,
+) -> Result<Vec<(String, Value)>> {
+    let to_entries = |pool: &IndexMap<String, Value>| {
+        pool.iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    };
+
+    match path {
+        "values" => return Ok(to_entries(&values.data)),
+        "vars" => return Ok(to_entries(&values.vars)),
+        _ => {}
+    }
+
+    let collection = lookup(value_pool, path)
+        .ok_or_else(|| anyhow!("Can not repeat over '{}': Not found in values", path))?;
+
+    let entries = collection.entries().ok_or_else(|| {
+        anyhow!(
+            "Can only repeat over 'values', 'vars', tables or arrays: Got '{}'",
+            path
+        )
+    })?;
+
+    Ok(entries
+        .into_iter()
+        .map(|(key, value)| (key, value.clone()))
+        .collect())
+}
+
+/// Compare numerically if both sides are numbers, otherwise lexicographically
+fn compare_text(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+fn resolve_key(
+    key: &str,
+    value_pool: &IndexMap<String, Value>,
+    meta: &TemplateMeta,
+) -> Option<Value> {
+    // Reserved 'meta.' and 'env.' namespaces take precedence over values
+    if let Some(meta_key) = key.strip_prefix("meta.") {
+        meta.get(meta_key).map(Value::String)
+    } else if let Some(var) = key.strip_prefix("env.") {
+        env::var(var).ok().map(Value::String)
+    } else {
+        lookup(value_pool, key).cloned()
+    }
+}
+
+fn fill_template(
+    template: &str,
+    value_pool: &IndexMap<String, Value>,
+    values_name: &str,
+    meta: &TemplateMeta,
+) -> Result<String> {
+    // Fill out template
+    let mut missing_keys: Vec<String> = Vec::new();
+    let mut collection_keys: Vec<String> = Vec::new();
+    let result = TEMPLATE_REGEX
+        .replace_all(template, |captures: &regex::Captures| {
+            let key = &captures[1];
+
+            if key.split_whitespace().count() != 1 {
+                // '{{' in a format string equals '{' so this returns '{{THE CAPTURE}}' essentially doing nothing
+                return format!("{{{{{}}}}}", &captures[1]);
+            }
+
+            let trimmed = key.trim_start_matches("-");
+            let dash_count = key.len() - trimmed.len();
+
+            match resolve_key(trimmed, value_pool, meta) {
+                Some(Value::String(value)) => remove_prefix(&value, dash_count).to_string(),
+                Some(_) => {
+                    collection_keys.push(trimmed.to_string());
+
+                    String::new()
+                }
+                None => {
+                    missing_keys.push(trimmed.to_string());
+
+                    String::new()
+                }
+            }
+        })
+        .to_string();
+
+    // Report keys that can only be repeated over
+    if !collection_keys.is_empty() {
+        return Err(anyhow!(
+            "Keys in values '{}' are tables or arrays and can not be inserted: {}",
+            values_name,
+            collection_keys.join(", "),
+        ));
+    }
+
+    // Report missing keys
+    if !missing_keys.is_empty() {
+        return Err(anyhow!(
+            "Could not find keys in values '{}': {}",
+            values_name,
+            missing_keys.join(", "),
+        ));
+    }
+
+    Ok(result)
+}
+
+fn current_date() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() / 86_400)
+        .unwrap_or_default() as i64;
+
+    date_from_days(days)
+}
+
+/// Format days since the epoch as a UTC calendar date
+fn date_from_days(days: i64) -> String {
+    // Convert days since the epoch to a UTC calendar date (Howard Hinnant's 'civil_from_days')
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn current_hostname() -> Option<String> {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .or_else(|| env::var("HOSTNAME").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+}
+
+fn remove_prefix(string: &str, amount: usize) -> &str {
+    let byte_index = string
+        .char_indices()
+        .nth(amount)
+        .map(|(index, _)| index)
+        .unwrap_or(string.len());
+
+    &string[byte_index..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{OutputFormat, SymlinkMode, TemplateMode};
+
+    const META: TemplateMeta = TemplateMeta {
+        project: "project",
+        values: "values",
+        template: "template",
+        out: None,
+    };
+
+    fn parse_values(values: &str) -> Values {
+        Values {
+            data: toml::from_str(values).unwrap(),
+            vars: IndexMap::new(),
+        }
+    }
+
+    fn render(template: &str, values: &str) -> Result<String> {
+        render_template(template, &parse_values(values), &META)
+    }
+
+    #[test]
+    fn recolor_only_replaces_colors_of_the_template() {
+        let template = Template {
+            name: "icon".to_string(),
+            source: TemplateSource::File {
+                path: PathBuf::from("icon.svg"),
+                contents: FileContents::Text(
+                    r##"<rect fill="#FF0000"/><text fill="{{ warn }}"/>"##.to_string(),
+                ),
+            },
+            out: Vec::new(),
+            values_map: None,
+            vars: IndexMap::new(),
+            recolor: IndexMap::from([("#ff0000".to_string(), "accent".to_string())]),
+        };
+        let values = parse_values("accent = \"#0000ff\"\nwarn = \"#ff0000\"");
+
+        assert_eq!(
+            render_source(&template, None, &values, &META).unwrap(),
+            br##"<rect fill="#0000ff"/><text fill="#ff0000"/>"##
+        );
+    }
+
+    #[test]
+    fn output_paths_are_expanded_after_filling_them() {
+        let template = Template {
+            name: "theme".to_string(),
+            source: TemplateSource::Format(OutputFormat::Toml),
+            out: Vec::new(),
+            values_map: None,
+            vars: IndexMap::new(),
+            recolor: IndexMap::new(),
+        };
+        let output = TemplateOutput {
+            path: PathBuf::from("{{ dir }}/theme.conf"),
+            mode: TemplateMode::Replace,
+            merge_path: Some(PathBuf::from("{{ merge }}")),
+            vars: IndexMap::new(),
+            permissions: None,
+            symlinks: SymlinkMode::Follow,
+            project_path: PathBuf::from("/tmp/w/p"),
+        };
+        let values = parse_values("dir = \"/tmp/w/abs\"\nmerge = \"merge.conf\"");
+
+        assert_eq!(
+            render_paths(&template, &output, &values, &META).unwrap(),
+            (
+                PathBuf::from("/tmp/w/abs/theme.conf"),
+                Some(PathBuf::from("/tmp/w/p/merge.conf"))
+            )
+        );
+    }
+
+    #[test]
+    fn dates_from_days_since_epoch() {
+        assert_eq!(date_from_days(0), "1970-01-01");
+        assert_eq!(date_from_days(-1), "1969-12-31");
+        assert_eq!(date_from_days(11_016), "2000-02-29");
+        assert_eq!(date_from_days(20_088), "2024-12-31");
+        assert_eq!(date_from_days(47_541), "2100-03-01");
+    }
+
+    #[test]
+    fn standalone_statements_remove_their_lines() {
+        let template = "start\n<{ repeat values }>\n{{ key }}={{ value }}\n<{ endrepeat }>\nend\n";
+
+        assert_eq!(
+            render(template, "a = 1\nb = 2").unwrap(),
+            "start\na=1\nb=2\nend\n"
+        );
+    }
+
+    #[test]
+    fn inline_statements_keep_surrounding_text() {
+        let template = r#"colors = [<{ repeat values }>"{{ value }}"<{ if not last }>, <{ endif }><{ endrepeat }>]"#;
+
+        assert_eq!(
+            render(template, "a = 1\nb = 2").unwrap(),
+            r#"colors = ["1", "2"]"#
+        );
+    }
+
+    #[test]
+    fn whitespace_control_trims_around_statements() {
+        assert_eq!(
+            render("a <{- if x -}> b <{- endif -}> c", "x = 1").unwrap(),
+            "abc"
+        );
+        assert_eq!(
+            render("a <{ if x }> b <{ endif }> c", "x = 1").unwrap(),
+            "a  b  c"
+        );
+    }
+
+    #[test]
+    fn nested_repeat_statements() {
+        let template = "<{ repeat groups as name, items }><{ repeat items as index, item }>{{ name }}.{{ item }} <{ endrepeat }><{ endrepeat }>";
+
+        assert_eq!(
+            render(template, "[groups]\none = [\"x\", \"y\"]\ntwo = [\"z\"]").unwrap(),
+            "one.x one.y two.z "
+        );
+    }
+
+    #[test]
+    fn item_names_take_precedence_over_loop_metadata() {
+        let values = "list = [\"a\", \"b\", \"c\"]";
+
+        assert_eq!(
+            render(
+                "<{ repeat list as index, item reverse }>{{ index }}:{{ item }} <{ endrepeat }>",
+                values
+            )
+            .unwrap(),
+            "2:c 1:b 0:a "
+        );
+        assert_eq!(
+            render(
+                "<{ repeat list as key, last reverse }>{{ index }}:{{ last }} <{ endrepeat }>",
+                values
+            )
+            .unwrap(),
+            "0:c 1:b 2:a "
+        );
+    }
+
+    #[test]
+    fn unterminated_and_unmatched_blocks_are_errors() {
+        let error = render("<{ repeat values }>\n{{ value }}\n", "a = 1").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("No endrepeat statement found after repeat statement"),
+            "{}",
+            error
+        );
+
+        let error = render("<{ if a }>\n<{ endrepeat }>\n<{ endif }>\n", "a = 1").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("Closing statement without matching opening statement"),
+            "{}",
+            error
+        );
+    }
+}